@@ -0,0 +1,48 @@
+use std::hint::black_box;
+
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use hive::{Board, Piece, Player, Point, State};
+
+fn ant_midgame() -> State {
+    let mut board = Board::default();
+    board.place(Point::new(0, 0, 0), Piece::Queen(Player::P1));
+    board.place(Point::new(0, 0, 1), Piece::Queen(Player::P2));
+    board.place(Point::new(-1, 0, 0), Piece::Ant(Player::P1));
+    board.place(Point::new(0, -1, 0), Piece::Ant(Player::P1));
+    board.place(Point::new(0, 0, 2), Piece::Ant(Player::P2));
+    board.place(Point::new(1, 0, 2), Piece::Spider(Player::P2));
+    board.place(Point::new(0, 0, -1), Piece::Grasshopper(Player::P1));
+    board.place(Point::new(-1, 0, 2), Piece::Grasshopper(Player::P2));
+    State::default()
+        .with_turn(4)
+        .with_active(Player::P1)
+        .with_queens(Some(Point::new(0, 0, 0)), Some(Point::new(0, 0, 1)))
+        .with_board(board)
+}
+
+fn bench_movegen(c: &mut Criterion) {
+    let opening = State::default();
+    c.bench_function("get_moves opening", |b| {
+        b.iter(|| black_box(&opening).get_moves())
+    });
+
+    let midgame = ant_midgame();
+    c.bench_function("get_moves ant midgame", |b| {
+        b.iter(|| black_box(&midgame).get_moves())
+    });
+
+    c.bench_function("perft 3", |b| b.iter(|| black_box(&opening).perft(3)));
+
+    let mut states = midgame.get_moves().into_iter().collect::<Vec<_>>();
+    states.extend(states.clone());
+    c.bench_function("sort states", |b| {
+        b.iter_batched_ref(
+            || states.clone(),
+            |states| states.sort(),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(benches, bench_movegen);
+criterion_main!(benches);
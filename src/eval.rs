@@ -0,0 +1,142 @@
+use crate::{PieceKind, Player, State};
+
+/// Per-kind piece values used by [`State::material_balance`]. The defaults are
+/// [`PieceKind::points`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PieceWeights {
+    pub queen: i32,
+    pub beetle: i32,
+    pub ant: i32,
+    pub grasshopper: i32,
+    pub spider: i32,
+    pub mosquito: i32,
+}
+
+impl PieceWeights {
+    pub fn weight(&self, kind: PieceKind) -> i32 {
+        match kind {
+            PieceKind::Queen => self.queen,
+            PieceKind::Beetle => self.beetle,
+            PieceKind::Ant => self.ant,
+            PieceKind::Grasshopper => self.grasshopper,
+            PieceKind::Spider => self.spider,
+            PieceKind::Mosquito => self.mosquito,
+        }
+    }
+}
+
+impl Default for PieceWeights {
+    fn default() -> Self {
+        Self {
+            queen: PieceKind::Queen.points(),
+            beetle: PieceKind::Beetle.points(),
+            ant: PieceKind::Ant.points(),
+            grasshopper: PieceKind::Grasshopper.points(),
+            spider: PieceKind::Spider.points(),
+            mosquito: PieceKind::Mosquito.points(),
+        }
+    }
+}
+
+/// Weighted count of the pieces each player could pick up and move, P1's minus P2's. A piece
+/// is stuck if it's covered or lifting it would split the hive, as in [`State::pins`]; a piece on
+/// top of a stack is always free to move.
+pub fn mobility_term(state: &State) -> i32 {
+    let weights = PieceWeights::default();
+    let mobility = |player| -> i32 {
+        let pins = state.pins(player);
+        state
+            .board
+            .iter()
+            .filter(|(point, stack)| {
+                stack.top().player() == player && (stack.height() > 1 || !pins.contains(*point))
+            })
+            .map(|(_, stack)| weights.weight(stack.top().kind()))
+            .sum()
+    };
+    mobility(Player::P1) - mobility(Player::P2)
+}
+
+/// A general-purpose evaluation from P1's point of view, suitable for
+/// [`search::best_move`](crate::search::best_move).
+pub fn default_eval(state: &State) -> i32 {
+    state.material_balance(&PieceWeights::default()) + mobility_term(state)
+}
+
+/// Scores positions for the search, from P1's point of view. Any `Fn(&State) -> i32` is an
+/// evaluator.
+pub trait Evaluator {
+    fn eval(&self, state: &State) -> i32;
+}
+
+impl<F: Fn(&State) -> i32> Evaluator for F {
+    fn eval(&self, state: &State) -> i32 {
+        self(state)
+    }
+}
+
+/// [`default_eval`] as an [`Evaluator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct DefaultEvaluator;
+
+impl Evaluator for DefaultEvaluator {
+    fn eval(&self, state: &State) -> i32 {
+        default_eval(state)
+    }
+}
+
+/// Scores positions by [`State::material_balance`] alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct MaterialEvaluator {
+    pub weights: PieceWeights,
+}
+
+impl Evaluator for MaterialEvaluator {
+    fn eval(&self, state: &State) -> i32 {
+        state.material_balance(&self.weights)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Board, Piece, Point};
+
+    #[test]
+    fn test_mobility_term() {
+        // each ant at the end of a line of four is free, one in the middle holds the hive together
+        let line = |kinds: [Piece; 4]| {
+            let board = (0..4)
+                .map(|x| Point::new(x, 0, 0))
+                .zip(kinds)
+                .collect::<Board>();
+            State::default().with_board(board)
+        };
+        let free = line([
+            Piece::Ant(Player::P1),
+            Piece::Queen(Player::P1),
+            Piece::Queen(Player::P2),
+            Piece::Ant(Player::P2),
+        ]);
+        let trapped = line([
+            Piece::Queen(Player::P1),
+            Piece::Ant(Player::P1),
+            Piece::Queen(Player::P2),
+            Piece::Ant(Player::P2),
+        ]);
+
+        assert_eq!(mobility_term(&free), 0);
+        assert_eq!(mobility_term(&trapped), -PieceKind::Ant.points());
+        assert!(default_eval(&trapped) < default_eval(&free));
+        assert_eq!(DefaultEvaluator.eval(&trapped), default_eval(&trapped));
+        assert_eq!(MaterialEvaluator::default().eval(&trapped), 0);
+
+        let mut covered = free.board.clone();
+        covered.place(Point::new(0, 0, 0), Piece::Beetle(Player::P2));
+        let covered = State::default().with_board(covered);
+        assert_eq!(
+            mobility_term(&covered),
+            -PieceKind::Ant.points() - PieceKind::Beetle.points()
+        );
+    }
+}
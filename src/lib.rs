@@ -1,20 +1,44 @@
-use std::{
-    collections::{HashMap, HashSet, VecDeque},
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::{
+    collections::VecDeque,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{
+    cmp::Reverse,
+    fmt::Display,
     hash::Hash,
-    iter::once,
-    ops::{Deref, DerefMut, Index, IndexMut, Not},
+    ops::{Add, Deref, DerefMut, Index, IndexMut, Not, Sub},
 };
 
+#[cfg(not(feature = "std"))]
+use hashbrown::{HashMap, HashSet, hash_map};
+#[cfg(feature = "std")]
+use std::collections::{HashMap, HashSet, hash_map};
+
 use itertools::Itertools;
+use rand::{Rng, RngExt};
+
+use crate::eval::PieceWeights;
+
+pub mod eval;
+pub mod search;
+#[cfg(feature = "std")]
+pub mod uhp;
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 #[repr(transparent)]
 pub struct Board {
-    map: HashMap<Point, Piece>,
+    map: HashMap<Point, Stack>,
 }
 
 impl Deref for Board {
-    type Target = HashMap<Point, Piece>;
+    type Target = HashMap<Point, Stack>;
 
     fn deref(&self) -> &Self::Target {
         &self.map
@@ -27,21 +51,376 @@ impl DerefMut for Board {
     }
 }
 
+impl<'a> IntoIterator for &'a Board {
+    type Item = (&'a Point, &'a Stack);
+    type IntoIter = hash_map::Iter<'a, Point, Stack>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.map.iter()
+    }
+}
+
+/// Pieces are placed in order, so a point that appears more than once ends up with a stack.
+impl FromIterator<(Point, Piece)> for Board {
+    fn from_iter<T: IntoIterator<Item = (Point, Piece)>>(iter: T) -> Self {
+        let mut board = Board::default();
+        board.extend(iter);
+        board
+    }
+}
+
+/// Like [`FromIterator`], each piece goes on top of whatever is already at its point.
+impl Extend<(Point, Piece)> for Board {
+    fn extend<T: IntoIterator<Item = (Point, Piece)>>(&mut self, iter: T) {
+        iter.into_iter()
+            .for_each(|(point, piece)| self.place(point, piece));
+    }
+}
+
+impl<const N: usize> From<[(Point, Piece); N]> for Board {
+    fn from(pieces: [(Point, Piece); N]) -> Self {
+        pieces.into_iter().collect()
+    }
+}
+
 impl Hash for Board {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.map.iter().sorted().for_each(|entry| entry.hash(state));
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.fingerprint().hash(state);
     }
 }
 
 impl PartialOrd for Board {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
 
+/// Boards are ordered by size, then [`Board::fingerprint`], and only unequal boards that tie on
+/// both are compared entry by entry.
 impl Ord for Board {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.map.iter().sorted().cmp(other.map.iter().sorted())
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.len()
+            .cmp(&other.len())
+            .then_with(|| self.fingerprint().cmp(&other.fingerprint()))
+            .then_with(|| {
+                if self == other {
+                    core::cmp::Ordering::Equal
+                } else {
+                    self.map.iter().sorted().cmp(other.map.iter().sorted())
+                }
+            })
+    }
+}
+
+impl Board {
+    /// Puts `piece` on top of whatever is at `point`, which is stored in canonical form however
+    /// it was written. Inserting into the map directly skips this, and leaves
+    /// [`State::validate_full`] to catch any non-canonical key.
+    pub fn place(&mut self, point: Point, piece: Piece) {
+        self.entry(point.canonicalize())
+            .or_insert_with(|| Stack(Vec::new()))
+            .0
+            .push(piece);
+    }
+
+    /// The piece on top of the stack at `point`, if there is one.
+    pub fn top(&self, point: &Point) -> Option<&Piece> {
+        self.get(point).map(Stack::top)
+    }
+
+    /// Every occupied hex with its whole stack, bottom to top, in no particular order.
+    pub fn cells(&self) -> impl Iterator<Item = (Point, &Stack)> {
+        self.map.iter().map(|(&point, stack)| (point, stack))
+    }
+
+    /// Moves the beetle on top of `from` onto the occupied hex `to`, leaving whatever it was
+    /// covering behind.
+    pub fn climb(&mut self, from: Point, to: Point) -> Result<(), HiveError> {
+        self.check_beetle(from)?;
+        if !self.contains_key(&to) {
+            return Err(HiveError::Empty(to));
+        }
+        self.move_top(from, to);
+        Ok(())
+    }
+
+    /// Moves the beetle on top of `from` down to the empty hex `to`.
+    pub fn descend(&mut self, from: Point, to: Point) -> Result<(), HiveError> {
+        self.check_beetle(from)?;
+        if self.contains_key(&to) {
+            return Err(HiveError::Occupied(to));
+        }
+        self.move_top(from, to);
+        Ok(())
+    }
+
+    fn check_beetle(&self, point: Point) -> Result<(), HiveError> {
+        match self.top(&point) {
+            None => Err(HiveError::Empty(point)),
+            Some(Piece::Beetle(_)) => Ok(()),
+            Some(_) => Err(HiveError::CannotClimb(point)),
+        }
+    }
+
+    fn move_top(&mut self, from: Point, to: Point) {
+        let stack = self.get_mut(&from).unwrap();
+        let piece = stack.0.pop().unwrap();
+        if stack.is_empty() {
+            self.remove(&from);
+        }
+        self.place(to, piece);
+    }
+
+    /// A copy of the board with only `player`'s pieces, which may not be connected. Mixed stacks
+    /// keep just that player's pieces, in the same order.
+    pub fn retain_player(&self, player: Player) -> Board {
+        Board {
+            map: self
+                .iter()
+                .filter_map(|(&point, stack)| {
+                    let pieces = stack
+                        .iter()
+                        .filter(|piece| piece.player() == player)
+                        .copied()
+                        .collect_vec();
+                    (!pieces.is_empty()).then_some((point, Stack(pieces)))
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether lifting the top piece off `point` would split the hive. Only the top of a stack
+    /// can move, and it always leaves the rest of the stack behind, so this is only ever true
+    /// for a lone piece on the ground. An empty hex never breaks the hive.
+    pub fn would_break_hive(&self, point: Point) -> bool {
+        match self.get(&point) {
+            None => false,
+            Some(stack) if stack.height() > 1 => false,
+            Some(_) => !self.without(point).is_connected(),
+        }
+    }
+
+    /// How many of the six hexes around `point` are occupied.
+    pub fn count_surrounding(&self, point: Point) -> usize {
+        point
+            .neighbors()
+            .iter()
+            .filter(|p| self.contains_key(*p))
+            .count()
+    }
+
+    /// Every hex with a piece of `kind` in it, whoever owns it and whether or not it's covered,
+    /// in ascending order.
+    pub fn piece_positions(&self, kind: PieceKind) -> Vec<Point> {
+        self.positions_where(|piece| piece.kind() == kind)
+    }
+
+    /// Like [`Board::piece_positions`], but only `player`'s pieces.
+    pub fn piece_positions_of(&self, kind: PieceKind, player: Player) -> Vec<Point> {
+        self.positions_where(|piece| piece.kind() == kind && piece.player() == player)
+    }
+
+    fn positions_where(&self, f: impl Fn(&Piece) -> bool) -> Vec<Point> {
+        self.iter()
+            .filter(|(_, stack)| stack.iter().any(&f))
+            .map(|(&point, _)| point)
+            .sorted()
+            .collect_vec()
+    }
+
+    /// The hexes around `point` with nothing in them.
+    pub fn empty_neighbors(&self, point: Point) -> impl Iterator<Item = Point> + '_ {
+        point
+            .neighbors()
+            .into_iter()
+            .filter(|p| !self.contains_key(p))
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.component_size(self.keys().nth(0).cloned()) == self.len()
+    }
+
+    /// Whether `other` is this board slid somewhere else on the grid.
+    pub fn equals_up_to_translation(&self, other: &Board) -> bool {
+        self.len() == other.len()
+            && self.translated(self.origin_offset()) == other.translated(other.origin_offset())
+    }
+
+    /// A copy with the stack at `point` taken away. Sliding pieces move over this so that they
+    /// can't lean on the hex they started from.
+    fn without(&self, point: Point) -> Board {
+        let mut board = self.clone();
+        board.remove(&point);
+        board
+    }
+
+    /// The offset that moves the corner of the bounding box, in axial coordinates, to the origin.
+    fn origin_offset(&self) -> Point {
+        let min_q = self.keys().map(|p| p.axial().0).min().unwrap_or(0);
+        let min_r = self.keys().map(|p| p.axial().1).min().unwrap_or(0);
+        Point::new(-min_q, 0, -min_r)
+    }
+
+    /// A digest of every stack and where it is, which doesn't depend on the order the map
+    /// happens to iterate in, or on anything else that changes from run to run.
+    fn fingerprint(&self) -> u64 {
+        self.map
+            .iter()
+            .map(|(point, stack)| {
+                let pieces = stack.iter().fold(0u64, |code, piece| {
+                    code << 4 | (piece.kind() as u64) << 1 | piece.player() as u64
+                });
+                let mut h = (point.x as u64)
+                    .wrapping_mul(0x9e37_79b9_7f4a_7c15)
+                    .wrapping_add((point.y as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f))
+                    .wrapping_add((point.z as u64).wrapping_mul(0x1656_67b1_9e37_79f9))
+                    ^ pieces;
+                // the splitmix64 finalizer
+                h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+                h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+                h ^ (h >> 31)
+            })
+            .fold(0, u64::wrapping_add)
+    }
+
+    fn translated(&self, offset: Point) -> Board {
+        self.mapped(|p| p + offset)
+    }
+
+    /// Every stack rotated `steps` times 60 degrees anticlockwise about the origin.
+    pub fn rotate60(&self, steps: i32) -> Board {
+        self.mapped(|p| p.rotate60(steps))
+    }
+
+    /// Every stack reflected in the line through the origin along `axis`.
+    pub fn reflect(&self, axis: Axis) -> Board {
+        self.mapped(|p| p.reflect(axis))
+    }
+
+    fn mapped(&self, f: impl Fn(Point) -> Point) -> Board {
+        Board {
+            map: self
+                .iter()
+                .map(|(&p, stack)| (f(p), stack.clone()))
+                .collect(),
+        }
+    }
+
+    /// The hexes whose stack holds the hive together, i.e. taking it away would split the board.
+    pub fn articulation_points(&self) -> HashSet<Point> {
+        struct Search {
+            graph: HashMap<Point, Vec<Point>>,
+            depth: HashMap<Point, usize>,
+            low: HashMap<Point, usize>,
+            points: HashSet<Point>,
+        }
+
+        fn visit(search: &mut Search, point: Point, parent: Option<Point>, depth: usize) {
+            search.depth.insert(point, depth);
+            search.low.insert(point, depth);
+            let mut children = 0;
+            for next in search.graph[&point].clone() {
+                if Some(next) == parent {
+                    continue;
+                }
+                let low = match search.depth.get(&next) {
+                    Some(&seen) => seen,
+                    None => {
+                        visit(search, next, Some(point), depth + 1);
+                        children += 1;
+                        if parent.is_some() && search.low[&next] >= depth {
+                            search.points.insert(point);
+                        }
+                        search.low[&next]
+                    }
+                };
+                let current = search.low.get_mut(&point).unwrap();
+                *current = (*current).min(low);
+            }
+            if parent.is_none() && children > 1 {
+                search.points.insert(point);
+            }
+        }
+
+        let mut search = Search {
+            graph: self.adjacency_graph(),
+            depth: HashMap::new(),
+            low: HashMap::new(),
+            points: HashSet::new(),
+        };
+        for &point in self.keys() {
+            if !search.depth.contains_key(&point) {
+                visit(&mut search, point, None, 0);
+            }
+        }
+        search.points
+    }
+
+    /// Maps every occupied hex to its occupied neighbors.
+    pub fn adjacency_graph(&self) -> HashMap<Point, Vec<Point>> {
+        self.keys()
+            .map(|point| {
+                let neighbors = point
+                    .neighbors()
+                    .into_iter()
+                    .filter(|p| self.contains_key(p))
+                    .collect();
+                (*point, neighbors)
+            })
+            .collect()
+    }
+
+    fn component_size(&self, point: Option<Point>) -> usize {
+        let Some(point) = point else {
+            return 0;
+        };
+        let mut q = VecDeque::new();
+        let mut visited = HashSet::new();
+        q.push_back(point);
+        visited.insert(point);
+        while let Some(point) = q.pop_front() {
+            point
+                .neighbors()
+                .into_iter()
+                .filter(|p| self.contains_key(p) && visited.insert(*p))
+                .for_each(|p| q.push_back(p));
+        }
+        visited.len()
+    }
+}
+
+/// The three lines through the origin along which [`Point`]s are generated: x runs east, y
+/// north-east and z north-west.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+/// The six ways out of a hex, in the same order as [`Point::neighbors`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Direction {
+    W,
+    SW,
+    SE,
+    E,
+    NE,
+    NW,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 6] = [
+        Direction::W,
+        Direction::SW,
+        Direction::SE,
+        Direction::E,
+        Direction::NE,
+        Direction::NW,
+    ];
+
+    pub fn opposite(&self) -> Direction {
+        Self::ALL[(*self as usize + 3) % 6]
     }
 }
 
@@ -76,6 +455,47 @@ impl IndexMut<usize> for Point {
     }
 }
 
+/// Treats `rhs` as an offset. The result is canonical.
+impl Add for Point {
+    type Output = Point;
+
+    fn add(self, rhs: Point) -> Self::Output {
+        Point::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z)
+    }
+}
+
+/// The offset from `rhs` to `self`. The result is canonical.
+impl Sub for Point {
+    type Output = Point;
+
+    fn sub(self, rhs: Point) -> Self::Output {
+        Point::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
+    }
+}
+
+impl Display for Point {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "({},{},{})", self.x, self.y, self.z)
+    }
+}
+
+/// Points are written as their two axial coordinates, since the third cube coordinate is
+/// redundant, and read back in canonical form.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Point {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.axial().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Point {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (q, r) = <(isize, isize)>::deserialize(deserializer)?;
+        Ok(Point::new(q, 0, r))
+    }
+}
+
 // TODO: every function that mutates a Point must canonicalize the result
 impl Point {
     pub fn new(x: isize, y: isize, z: isize) -> Self {
@@ -83,13 +503,18 @@ impl Point {
     }
 
     pub fn canonicalize(&self) -> Self {
-        let mut new = *self;
-        while new.y > 0 && new.z < 0 {
-            new.y -= 1;
-            new.z += 1;
-            new.x += 1;
+        // (1, -1, 1) is the zero vector, so shift by the median of (-x, y, -z) to get the unique
+        // shortest representation of the hex
+        let shift = [-self.x, self.y, -self.z]
+            .into_iter()
+            .sorted()
+            .nth(1)
+            .unwrap();
+        Self {
+            x: self.x + shift,
+            y: self.y - shift,
+            z: self.z + shift,
         }
-        new
     }
 
     pub fn neighbors(&self) -> Vec<Self> {
@@ -104,6 +529,76 @@ impl Point {
             .collect()
     }
 
+    /// Each neighbour paired with the direction it lies in from this point.
+    pub fn neighbors_with_direction(&self) -> [(Point, Direction); 6] {
+        let neighbors = self.neighbors();
+        Direction::ALL.map(|direction| (neighbors[direction as usize], direction))
+    }
+
+    fn axial(&self) -> (isize, isize) {
+        (self.x + self.y, self.z + self.y)
+    }
+
+    /// Rotated `steps` times 60 degrees anticlockwise about the origin. Negative steps turn
+    /// clockwise.
+    pub fn rotate60(&self, steps: i32) -> Self {
+        (0..steps.rem_euclid(6)).fold(*self, |p, _| Self::new(-p.z, p.x, p.y))
+    }
+
+    /// Reflected in the line through the origin along `axis`.
+    pub fn reflect(&self, axis: Axis) -> Self {
+        let steps = match axis {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2,
+        };
+        let p = self.rotate60(-steps);
+        Self::new(p.x, -p.z, -p.y).rotate60(steps)
+    }
+
+    pub fn distance(&self, other: &Point) -> usize {
+        let diff = *self - *other;
+        diff.x.unsigned_abs() + diff.y.unsigned_abs() + diff.z.unsigned_abs()
+    }
+
+    /// The hexes exactly `radius` steps away, walking around the ring in `neighbors` order.
+    pub fn ring(&self, radius: usize) -> Vec<Self> {
+        if radius == 0 {
+            return vec![*self];
+        }
+        let mut hex = *self;
+        for _ in 0..radius {
+            hex = hex.neighbors()[4];
+        }
+        let mut ring = Vec::with_capacity(6 * radius);
+        for side in 0..6 {
+            for _ in 0..radius {
+                ring.push(hex);
+                hex = hex.neighbors()[side];
+            }
+        }
+        ring
+    }
+
+    /// The hexes at most `radius` steps away, from the center outwards.
+    pub fn spiral(&self, radius: usize) -> Vec<Self> {
+        (0..=radius).flat_map(|r| self.ring(r)).collect_vec()
+    }
+
+    /// The empty neighbors a piece here could slide to in one step. The gap has to be wide
+    /// enough to fit through, and the piece has to keep touching the hive as it goes, so exactly
+    /// one of the two hexes either side of the step must be occupied. `board` shouldn't contain
+    /// the sliding piece.
+    fn slides(&self, board: &Board) -> impl Iterator<Item = Point> {
+        let neighbors = self.neighbors();
+        (0..6).filter_map(move |i| {
+            let free = !board.contains_key(&neighbors[i])
+                && board.contains_key(&neighbors[(i + 5) % 6])
+                    != board.contains_key(&neighbors[(i + 1) % 6]);
+            free.then_some(neighbors[i])
+        })
+    }
+
     pub fn movable_neighbors(&self, board: &Board) -> impl Iterator<Item = Point> {
         self.neighbors()
             .into_iter()
@@ -111,7 +606,7 @@ impl Point {
             .circular_tuple_windows()
             .filter(|((_, a), (_, b))| *a && *b)
             .flat_map(|((p1, _), (p2, _))| vec![p1, p2])
-            .unique()
+            .distinct()
     }
 }
 
@@ -121,6 +616,36 @@ pub enum Player {
     P2,
 }
 
+impl Player {
+    fn letter(&self) -> char {
+        match self {
+            Self::P1 => 'w',
+            Self::P2 => 'b',
+        }
+    }
+
+    fn from_letter(letter: char) -> Option<Self> {
+        match letter {
+            'w' => Some(Self::P1),
+            'b' => Some(Self::P2),
+            _ => None,
+        }
+    }
+    /// `0` for P1 and `1` for P2, for arrays with an entry per player.
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    /// The inverse of [`Player::index`].
+    pub fn from_index(index: usize) -> Option<Self> {
+        match index {
+            0 => Some(Self::P1),
+            1 => Some(Self::P2),
+            _ => None,
+        }
+    }
+}
+
 impl Not for Player {
     type Output = Self;
 
@@ -132,390 +657,4770 @@ impl Not for Player {
     }
 }
 
+/// The pieces on a single hex, bottom to top. Stacks on a [`Board`] are never empty.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct Stack(Vec<Piece>);
+
+impl Deref for Stack {
+    type Target = [Piece];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<Piece> for Stack {
+    fn from(piece: Piece) -> Self {
+        Self(vec![piece])
+    }
+}
+
+impl Stack {
+    pub fn top(&self) -> &Piece {
+        self.last().expect("stacks are never empty")
+    }
+
+    pub fn height(&self) -> usize {
+        self.len()
+    }
+
+    fn flipped(&self) -> Self {
+        Self(self.iter().map(Piece::flipped).collect())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Piece {
     Queen(Player),
-    Beetle(Player, Option<Box<Piece>>),
+    Beetle(Player),
     Ant(Player),
     Grasshopper(Player),
     Spider(Player),
+    Mosquito(Player),
 }
 
 impl Piece {
+    pub fn new(kind: PieceKind, player: Player) -> Self {
+        match kind {
+            PieceKind::Queen => Self::Queen(player),
+            PieceKind::Beetle => Self::Beetle(player),
+            PieceKind::Ant => Self::Ant(player),
+            PieceKind::Grasshopper => Self::Grasshopper(player),
+            PieceKind::Spider => Self::Spider(player),
+            PieceKind::Mosquito => Self::Mosquito(player),
+        }
+    }
+
     pub fn player(&self) -> Player {
         match self {
             Self::Queen(player) => *player,
-            Self::Beetle(player, _) => *player,
+            Self::Beetle(player) => *player,
             Self::Ant(player) => *player,
             Self::Grasshopper(player) => *player,
             Self::Spider(player) => *player,
+            Self::Mosquito(player) => *player,
         }
     }
-}
-
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct Pieces {
-    p1: Vec<Piece>,
-    p2: Vec<Piece>,
-}
 
-impl Pieces {
-    pub fn new() -> Self {
-        Self {
-            p1: vec![
-                [Piece::Queen(Player::P1); 1].as_slice(),
-                [const { Piece::Beetle(Player::P1, None) }; 2].as_slice(),
-                [const { Piece::Ant(Player::P1) }; 3].as_slice(),
-                [const { Piece::Grasshopper(Player::P1) }; 3].as_slice(),
-                [const { Piece::Spider(Player::P1) }; 2].as_slice(),
-            ]
-            .into_iter()
-            .flatten()
-            .cloned()
-            .collect_vec(),
-            p2: vec![
-                [Piece::Queen(Player::P2); 1].as_slice(),
-                [const { Piece::Beetle(Player::P2, None) }; 2].as_slice(),
-                [const { Piece::Ant(Player::P2) }; 3].as_slice(),
-                [const { Piece::Grasshopper(Player::P2) }; 3].as_slice(),
-                [const { Piece::Spider(Player::P2) }; 2].as_slice(),
-            ]
-            .into_iter()
-            .flatten()
-            .cloned()
-            .collect_vec(),
+    pub fn flipped(&self) -> Self {
+        match self {
+            Self::Queen(player) => Self::Queen(!*player),
+            Self::Beetle(player) => Self::Beetle(!*player),
+            Self::Ant(player) => Self::Ant(!*player),
+            Self::Grasshopper(player) => Self::Grasshopper(!*player),
+            Self::Spider(player) => Self::Spider(!*player),
+            Self::Mosquito(player) => Self::Mosquito(!*player),
         }
     }
 
-    pub fn remove(&mut self, player: Player, idx: usize) -> Piece {
-        // FIXME: using swap_remove here breaks equality checks later
-        // figure out if the extra O(n) here outweighs the alternative O(n log n) of sorting at
-        // check time
-        match player {
-            Player::P1 => self.p1.remove(idx),
-            Player::P2 => self.p2.remove(idx),
+    pub fn kind(&self) -> PieceKind {
+        match self {
+            Self::Queen(_) => PieceKind::Queen,
+            Self::Beetle(_) => PieceKind::Beetle,
+            Self::Ant(_) => PieceKind::Ant,
+            Self::Grasshopper(_) => PieceKind::Grasshopper,
+            Self::Spider(_) => PieceKind::Spider,
+            Self::Mosquito(_) => PieceKind::Mosquito,
         }
     }
-}
 
-impl Default for Pieces {
-    fn default() -> Self {
-        Self::new()
+    /// The player's colour, `w` or `b`, followed by [`PieceKind::ascii`], e.g. `wQ`.
+    pub fn ascii(&self) -> String {
+        format!("{}{}", self.player().letter(), self.kind().ascii())
+    }
+
+    /// Like [`Piece::ascii`], but with [`PieceKind::unicode`] after the colour, e.g. `w🐝`.
+    pub fn unicode(&self) -> String {
+        format!("{}{}", self.player().letter(), self.kind().unicode())
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct State {
-    turn: usize,
-    active: Player,
-    p1_queen: Option<Point>,
-    p2_queen: Option<Point>,
-    unplaced: Pieces,
-    board: Board,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PieceKind {
+    Queen,
+    Beetle,
+    Ant,
+    Grasshopper,
+    Spider,
+    Mosquito,
 }
 
-impl State {
-    pub fn new(
-        turn: Option<usize>,
-        active: Player,
-        p1_queen: Option<Point>,
-        p2_queen: Option<Point>,
-        unplaced: Pieces,
-        board: Board,
-    ) -> Self {
-        Self {
+impl PieceKind {
+    pub const ALL: [PieceKind; 6] = [
+        PieceKind::Queen,
+        PieceKind::Beetle,
+        PieceKind::Ant,
+        PieceKind::Grasshopper,
+        PieceKind::Spider,
+        PieceKind::Mosquito,
+    ];
+
+    /// Standard piece values for evaluation and move ordering. The queen is worth nothing on its
+    /// own since her safety is scored separately.
+    ///
+    /// | kind        | points |
+    /// |-------------|--------|
+    /// | Queen       | 0      |
+    /// | Beetle      | 3      |
+    /// | Ant         | 4      |
+    /// | Grasshopper | 2      |
+    /// | Spider      | 2      |
+    /// | Mosquito    | 3      |
+    pub const fn points(&self) -> i32 {
+        match self {
+            Self::Queen => 0,
+            Self::Beetle => 3,
+            Self::Ant => 4,
+            Self::Grasshopper => 2,
+            Self::Spider => 2,
+            Self::Mosquito => 3,
+        }
+    }
+
+    /// The letter used for this kind in UHP and position strings.
+    pub fn ascii(&self) -> char {
+        match self {
+            Self::Queen => 'Q',
+            Self::Beetle => 'B',
+            Self::Ant => 'A',
+            Self::Grasshopper => 'G',
+            Self::Spider => 'S',
+            Self::Mosquito => 'M',
+        }
+    }
+
+    /// An emoji for this kind, for renderers that can show one.
+    pub fn unicode(&self) -> char {
+        match self {
+            Self::Queen => '🐝',
+            Self::Beetle => '🪲',
+            Self::Ant => '🐜',
+            Self::Grasshopper => '🦗',
+            Self::Spider => '🕷',
+            Self::Mosquito => '🦟',
+        }
+    }
+
+    fn from_letter(letter: char) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.ascii() == letter)
+    }
+
+    pub fn movement_rule(&self) -> MovementRule {
+        match self {
+            Self::Queen => MovementRule {
+                description: "slides one space around the hive",
+                slides: true,
+                climbs: false,
+                jumps: false,
+                distance: Some(1),
+                special_ability: false,
+            },
+            Self::Beetle => MovementRule {
+                description: "moves one space, including onto and off of the hive",
+                slides: true,
+                climbs: true,
+                jumps: false,
+                distance: Some(1),
+                special_ability: false,
+            },
+            Self::Ant => MovementRule {
+                description: "slides any number of spaces around the hive",
+                slides: true,
+                climbs: false,
+                jumps: false,
+                distance: None,
+                special_ability: false,
+            },
+            Self::Grasshopper => MovementRule {
+                description: "jumps in a straight line over one or more pieces",
+                slides: false,
+                climbs: false,
+                jumps: true,
+                distance: None,
+                special_ability: false,
+            },
+            Self::Spider => MovementRule {
+                description: "slides exactly three spaces around the hive",
+                slides: true,
+                climbs: false,
+                jumps: false,
+                distance: Some(3),
+                special_ability: false,
+            },
+            Self::Mosquito => MovementRule {
+                description: "moves like any piece it touches, or as a beetle while on top of the hive",
+                slides: true,
+                climbs: true,
+                jumps: true,
+                distance: None,
+                special_ability: true,
+            },
+        }
+    }
+}
+
+/// How a kind of piece gets around. `distance` is the exact number of steps a move takes, or
+/// `None` when it varies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MovementRule {
+    pub description: &'static str,
+    pub slides: bool,
+    pub climbs: bool,
+    pub jumps: bool,
+    pub distance: Option<usize>,
+    pub special_ability: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Move {
+    Place {
+        kind: PieceKind,
+        to: Point,
+    },
+    Relocate {
+        kind: PieceKind,
+        from: Point,
+        to: Point,
+    },
+    Pass,
+}
+
+impl Move {
+    pub fn is_placement(&self) -> bool {
+        matches!(self, Self::Place { .. })
+    }
+
+    pub fn is_movement(&self) -> bool {
+        matches!(self, Self::Relocate { .. })
+    }
+
+    pub fn is_pass(&self) -> bool {
+        matches!(self, Self::Pass)
+    }
+
+    pub fn piece_kind(&self) -> Option<PieceKind> {
+        match self {
+            Self::Place { kind, .. } | Self::Relocate { kind, .. } => Some(*kind),
+            Self::Pass => None,
+        }
+    }
+
+    pub fn destination(&self) -> Option<Point> {
+        match self {
+            Self::Place { to, .. } | Self::Relocate { to, .. } => Some(*to),
+            Self::Pass => None,
+        }
+    }
+
+    /// The same move with its points sent through `f`.
+    pub(crate) fn mapped(self, f: impl Fn(Point) -> Point) -> Move {
+        match self {
+            Self::Place { kind, to } => Self::Place { kind, to: f(to) },
+            Self::Relocate { kind, from, to } => Self::Relocate {
+                kind,
+                from: f(from),
+                to: f(to),
+            },
+            Self::Pass => Self::Pass,
+        }
+    }
+
+    /// A readable description such as `Place Ant at (0,0,2)` or `Move Beetle onto Spider`,
+    /// for showing to people rather than engines. `state` is the position the move is played
+    /// from.
+    pub fn notation_short(&self, state: &State) -> String {
+        match *self {
+            Self::Place { kind, to } => format!("Place {kind:?} at {to}"),
+            Self::Relocate { kind, to, .. } => match state.board.top(&to) {
+                Some(under) => format!("Move {kind:?} onto {:?}", under.kind()),
+                None => format!("Move {kind:?} to {to}"),
+            },
+            Self::Pass => "Pass".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Pieces {
+    p1: Vec<Piece>,
+    p2: Vec<Piece>,
+}
+
+impl Pieces {
+    /// The order kinds are offered in when placing: the queen, spider, beetle, grasshopper and
+    /// ant of the base game, then the expansion pieces.
+    pub const KIND_ORDER: [PieceKind; 6] = [
+        PieceKind::Queen,
+        PieceKind::Spider,
+        PieceKind::Beetle,
+        PieceKind::Grasshopper,
+        PieceKind::Ant,
+        PieceKind::Mosquito,
+    ];
+
+    pub fn new() -> Self {
+        Self {
+            p1: vec![
+                [Piece::Queen(Player::P1); 1].as_slice(),
+                [Piece::Beetle(Player::P1); 2].as_slice(),
+                [Piece::Ant(Player::P1); 3].as_slice(),
+                [Piece::Grasshopper(Player::P1); 3].as_slice(),
+                [Piece::Spider(Player::P1); 2].as_slice(),
+            ]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect_vec(),
+            p2: vec![
+                [Piece::Queen(Player::P2); 1].as_slice(),
+                [Piece::Beetle(Player::P2); 2].as_slice(),
+                [Piece::Ant(Player::P2); 3].as_slice(),
+                [Piece::Grasshopper(Player::P2); 3].as_slice(),
+                [Piece::Spider(Player::P2); 2].as_slice(),
+            ]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect_vec(),
+        }
+    }
+
+    /// Builds a hand for `player` from per-kind counts, leaving the other player's hand empty.
+    pub fn from_counts(counts: &HashMap<PieceKind, usize>, player: Player) -> Self {
+        Self {
+            p1: Vec::new(),
+            p2: Vec::new(),
+        }
+        .with_counts(counts, player)
+    }
+
+    pub fn with_counts(mut self, counts: &HashMap<PieceKind, usize>, player: Player) -> Self {
+        let hand = counts
+            .iter()
+            .sorted()
+            .flat_map(|(&kind, &count)| vec![Piece::new(kind, player); count])
+            .collect_vec();
+        match player {
+            Player::P1 => self.p1 = hand,
+            Player::P2 => self.p2 = hand,
+        }
+        self
+    }
+
+    fn hand(&self, player: Player) -> &[Piece] {
+        match player {
+            Player::P1 => &self.p1,
+            Player::P2 => &self.p2,
+        }
+    }
+
+    /// The kinds `player` still has in hand, each once, in [`Pieces::KIND_ORDER`].
+    pub fn kinds(&self, player: Player) -> impl Iterator<Item = PieceKind> + '_ {
+        let hand = self.hand(player);
+        Self::KIND_ORDER
+            .into_iter()
+            .filter(|&kind| hand.iter().any(|piece| piece.kind() == kind))
+    }
+
+    pub fn flipped(&self) -> Self {
+        Self {
+            p1: self.p2.iter().map(Piece::flipped).collect_vec(),
+            p2: self.p1.iter().map(Piece::flipped).collect_vec(),
+        }
+    }
+
+    pub fn remove(&mut self, player: Player, idx: usize) -> Piece {
+        // FIXME: using swap_remove here breaks equality checks later
+        // figure out if the extra O(n) here outweighs the alternative O(n log n) of sorting at
+        // check time
+        match player {
+            Player::P1 => self.p1.remove(idx),
+            Player::P2 => self.p2.remove(idx),
+        }
+    }
+}
+
+impl Default for Pieces {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HiveError {
+    IllegalMove(Move),
+    InvalidNotation(String),
+    WrongPlayer(Player),
+    Empty(Point),
+    Occupied(Point),
+    CannotClimb(Point),
+    /// A point that isn't in [`Point::canonicalize`]d form.
+    NonCanonical(Point),
+    /// A stack with nothing in it, or something other than a beetle or mosquito off the ground.
+    InvalidStack(Point),
+    Disconnected,
+    /// The state's record of where the queen is disagrees with the board.
+    QueenMismatch(Player),
+    /// The player's hand and board don't add up to the full set of this kind.
+    PieceCount(Player, PieceKind),
+    /// The player has none of this kind left to place.
+    NotInHand(Player, PieceKind),
+    /// An empty point that isn't next to the hive, or touches the opponent.
+    NotPlaceable(Point),
+    /// The player has run out of turns to place their queen and must place it now.
+    QueenRequired(Player),
+    /// The player's piece here is covered, or lifting it would split the hive.
+    Pinned(Point),
+}
+
+impl Display for HiveError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::IllegalMove(mv) => write!(f, "illegal move: {mv:?}"),
+            Self::InvalidNotation(s) => write!(f, "invalid move notation: {s:?}"),
+            Self::WrongPlayer(player) => write!(f, "it is not {player:?}'s turn"),
+            Self::Empty(point) => write!(f, "no piece at {point:?}"),
+            Self::Occupied(point) => write!(f, "{point:?} is occupied"),
+            Self::CannotClimb(point) => write!(f, "the piece at {point:?} cannot climb"),
+            Self::NonCanonical(point) => write!(f, "{point:?} is not canonical"),
+            Self::InvalidStack(point) => write!(f, "the stack at {point:?} is malformed"),
+            Self::Disconnected => write!(f, "the hive is not connected"),
+            Self::QueenMismatch(player) => {
+                write!(f, "{player:?}'s queen is not where the state says")
+            }
+            Self::PieceCount(player, kind) => {
+                write!(f, "{player:?} has the wrong number of {kind:?} pieces")
+            }
+            Self::NotInHand(player, kind) => write!(f, "{player:?} has no {kind:?} left to place"),
+            Self::NotPlaceable(point) => write!(f, "a piece cannot be placed at {point:?}"),
+            Self::QueenRequired(player) => write!(f, "{player:?} must place their queen"),
+            Self::Pinned(point) => write!(f, "the piece at {point:?} is pinned"),
+        }
+    }
+}
+
+impl core::error::Error for HiveError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GameResult {
+    InProgress,
+    P1Wins,
+    P2Wins,
+    Draw,
+}
+
+impl GameResult {
+    pub fn winner(&self) -> Option<Player> {
+        match self {
+            Self::P1Wins => Some(Player::P1),
+            Self::P2Wins => Some(Player::P2),
+            Self::InProgress | Self::Draw => None,
+        }
+    }
+
+    pub fn is_draw(&self) -> bool {
+        *self == Self::Draw
+    }
+
+    pub fn is_over(&self) -> bool {
+        *self != Self::InProgress
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Rules {
+    /// The game is drawn once `turn` exceeds this. `None` plays on indefinitely.
+    pub max_turns: Option<usize>,
+    /// Each player must have placed their queen by the end of their `n`th turn, counting from one
+    /// as the rules do, so the official `Some(4)` forces it on the fourth turn at the latest.
+    /// `None` lets the queen come down whenever.
+    pub queen_by_turn: Option<usize>,
+    /// Whether pieces may climb onto the hive. Without it, beetles and mosquitoes copying them
+    /// only walk on the ground like a queen.
+    pub climbing: bool,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            max_turns: None,
+            queen_by_turn: Some(4),
+            climbing: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BranchingFactorStats {
+    pub placements: usize,
+    pub relocations: usize,
+    /// The most moves available to any single piece.
+    pub max_destinations: usize,
+}
+
+/// Which players could get a piece onto a hex on their next turn, by placing one or by moving one
+/// already on the board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Coverage {
+    pub p1_place: bool,
+    pub p1_move: bool,
+    pub p2_place: bool,
+    pub p2_move: bool,
+}
+
+impl Coverage {
+    pub fn reachable_by(&self, player: Player) -> bool {
+        match player {
+            Player::P1 => self.p1_place || self.p1_move,
+            Player::P2 => self.p2_place || self.p2_move,
+        }
+    }
+}
+
+/// Each queen's [liberties](State::liberties) before and after a move, `None` while she's in
+/// hand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct LibertyDelta {
+    pub p1_before: Option<usize>,
+    pub p1_after: Option<usize>,
+    pub p2_before: Option<usize>,
+    pub p2_after: Option<usize>,
+}
+
+impl LibertyDelta {
+    /// How many liberties `player`'s queen gained, negative if she lost some. `None` unless she
+    /// was on the board both before and after.
+    pub fn change(&self, player: Player) -> Option<isize> {
+        let (before, after) = match player {
+            Player::P1 => (self.p1_before, self.p1_after),
+            Player::P2 => (self.p2_before, self.p2_after),
+        };
+        Some(after? as isize - before? as isize)
+    }
+}
+
+/// Every piece put down so far, in the order it was placed, with the point and height in its
+/// stack where it is now. This is what keeps a piece's notation number, like the 2 in `wA2`, the
+/// same however it moves. It's only bookkeeping for notation, so states compare and hash the
+/// same whatever it holds.
+#[derive(Debug, Clone, Default)]
+struct PlacementOrder(Vec<(Piece, Point, usize)>);
+
+impl PartialEq for PlacementOrder {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for PlacementOrder {}
+
+impl PartialOrd for PlacementOrder {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PlacementOrder {
+    fn cmp(&self, _: &Self) -> core::cmp::Ordering {
+        core::cmp::Ordering::Equal
+    }
+}
+
+impl Hash for PlacementOrder {
+    fn hash<H: core::hash::Hasher>(&self, _: &mut H) {}
+}
+
+impl PlacementOrder {
+    /// Where in the order the piece at `height` in the stack at `point` was placed. `None` for
+    /// pieces that weren't placed through move generation, e.g. ones on a board set up directly,
+    /// and for stale entries that no longer match the board.
+    #[cfg(feature = "std")]
+    fn rank(&self, board: &Board, point: Point, height: usize) -> Option<usize> {
+        self.0.iter().position(|&(piece, p, h)| {
+            (p, h) == (point, height)
+                && board.get(&point).and_then(|stack| stack.get(height)) == Some(&piece)
+        })
+    }
+
+    fn moved(&mut self, from: (Point, usize), to: (Point, usize)) {
+        if let Some(entry) = self.0.iter_mut().find(|(_, p, h)| (*p, *h) == from) {
+            (entry.1, entry.2) = to;
+        }
+    }
+}
+
+/// A rotation about the origin, optionally after a reflection, followed by a translation: how
+/// [`State::canonical`] gets from a position to its representative.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Symmetry {
+    rotations: i32,
+    reflect: bool,
+    offset: Point,
+}
+
+impl Symmetry {
+    pub(crate) fn apply(&self, p: Point) -> Point {
+        let p = if self.reflect { p.reflect(Axis::X) } else { p };
+        p.rotate60(self.rotations) + self.offset
+    }
+
+    pub(crate) fn invert(&self, p: Point) -> Point {
+        let p = (p - self.offset).rotate60(-self.rotations);
+        if self.reflect { p.reflect(Axis::X) } else { p }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct State {
+    turn: usize,
+    active: Player,
+    /// Where each player's queen is, indexed by [`Player::index`].
+    queens: [Option<Point>; 2],
+    unplaced: Pieces,
+    board: Board,
+    rules: Rules,
+    placed: PlacementOrder,
+}
+
+impl State {
+    pub fn new(
+        turn: Option<usize>,
+        active: Player,
+        p1_queen: Option<Point>,
+        p2_queen: Option<Point>,
+        unplaced: Pieces,
+        board: Board,
+    ) -> Self {
+        Self {
             turn: turn.unwrap_or(0),
             active,
-            p1_queen,
-            p2_queen,
+            queens: [p1_queen, p2_queen].map(|queen| queen.map(|p| p.canonicalize())),
             unplaced,
             board,
+            rules: Rules::default(),
+            placed: PlacementOrder::default(),
+        }
+    }
+
+    /// The start of a game: an empty board, both full hands, and P1 to move. The same as
+    /// [`State::default`].
+    pub fn empty_board_opening() -> Self {
+        Self::default()
+    }
+
+    /// Builder-style setters for assembling positions outside the crate.
+    ///
+    /// ```
+    /// use hive::{Board, Piece, Player, Point, State};
+    ///
+    /// let mut board = Board::default();
+    /// board.place(Point::new(0, 0, 0), Piece::Queen(Player::P1));
+    /// board.place(Point::new(0, 0, 1), Piece::Queen(Player::P2));
+    /// board.place(Point::new(-1, 0, 0), Piece::Ant(Player::P1));
+    /// board.place(Point::new(0, 0, 2), Piece::Spider(Player::P2));
+    ///
+    /// let state = State::default()
+    ///     .with_turn(2)
+    ///     .with_active(Player::P1)
+    ///     .with_queens(Some(Point::new(0, 0, 0)), Some(Point::new(0, 0, 1)))
+    ///     .with_board(board);
+    ///
+    /// assert!(state.validate());
+    /// assert!(!state.get_moves().is_empty());
+    /// ```
+    pub fn with_active(mut self, active: Player) -> Self {
+        self.active = active;
+        self
+    }
+
+    pub fn with_turn(mut self, turn: usize) -> Self {
+        self.turn = turn;
+        self
+    }
+
+    pub fn with_board(mut self, board: Board) -> Self {
+        self.board = board;
+        self
+    }
+
+    pub fn with_queens(mut self, p1_queen: Option<Point>, p2_queen: Option<Point>) -> Self {
+        self.queens = [p1_queen, p2_queen].map(|queen| queen.map(|p| p.canonicalize()));
+        self
+    }
+
+    pub fn with_unplaced(mut self, unplaced: Pieces) -> Self {
+        self.unplaced = unplaced;
+        self
+    }
+
+    pub fn with_rules(mut self, rules: Rules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    /// Whether the side to move has yet to put a piece on the board. Their first placement may
+    /// go anywhere next to the hive, even against the opponent.
+    pub fn is_first_move(&self) -> bool {
+        !self
+            .board
+            .values()
+            .flat_map(|stack| stack.iter())
+            .any(|piece| piece.player() == self.active)
+    }
+
+    /// Whether this is one of the first four plies, each player's first two placements.
+    pub fn is_opening(&self) -> bool {
+        self.ply() < 4
+    }
+
+    /// How many moves, passes included, have been played to reach this position. `turn` only
+    /// counts full moves; P1 always moves first.
+    pub fn ply(&self) -> usize {
+        2 * self.turn + self.active.index()
+    }
+
+    /// The position after the side to move plays onto `board`. `queen` is where the mover's own
+    /// queen now is, if it was placed or moved. Relocations work out for themselves whose queen
+    /// went where, since one side's piece can end up moving the other's.
+    pub fn next_turn(&self, queen: Option<Point>, unplaced: Option<Pieces>, board: Board) -> Self {
+        let mut next = Self {
+            turn: if self.active == Player::P2 {
+                self.turn + 1
+            } else {
+                self.turn
+            },
+            active: !self.active,
+            queens: self.queens,
+            unplaced: unplaced.unwrap_or_else(|| self.unplaced.clone()),
+            board,
+            rules: self.rules,
+            placed: self.placed.clone(),
+        };
+        if queen.is_some() {
+            next.set_queen(self.active, queen);
         }
+        next
+    }
+
+    /// Where the side to move may place a piece: the origin on an empty board, anywhere next to
+    /// the hive for a player who hasn't placed yet, and otherwise only next to their own pieces
+    /// and away from all the opponent's. So P2's first piece may go on any of the six sides of
+    /// P1's; those are all the same up to symmetry, and [`State::canonical_moves`] keeps one.
+    pub fn placeable_points(&self) -> Vec<Point> {
+        if self.board.is_empty() {
+            return vec![Point::new(0, 0, 0)];
+        }
+        if self.is_first_move() {
+            return self
+                .board
+                .keys()
+                .flat_map(|&point| self.board.empty_neighbors(point))
+                .distinct()
+                .collect_vec();
+        }
+        self.board
+            .iter()
+            .filter(|(_, stack)| stack.top().player() == self.active)
+            .flat_map(|(&point, _)| self.board.empty_neighbors(point))
+            .distinct()
+            .filter(|point| {
+                point.neighbors().iter().all(|p| {
+                    self.board
+                        .top(p)
+                        .is_none_or(|piece| piece.player() == self.active)
+                })
+            })
+            .collect_vec()
+    }
+
+    /// A stricter [`State::validate`] for positions that came from outside, e.g. parsed or
+    /// deserialized ones. Besides the hive being in one piece, every point must be canonical,
+    /// only beetles and mosquitoes can be off the ground, the queen fields must match the board,
+    /// and each player's hand and board together must make up exactly their set in `full`, e.g.
+    /// [`Pieces::new`] for the base game. Queen timing isn't checked.
+    pub fn validate_full(&self, full: &Pieces) -> Result<(), HiveError> {
+        for (&point, stack) in &self.board {
+            if point != point.canonicalize() {
+                return Err(HiveError::NonCanonical(point));
+            }
+            if stack.is_empty()
+                || stack[1..]
+                    .iter()
+                    .any(|piece| !matches!(piece, Piece::Beetle(_) | Piece::Mosquito(_)))
+            {
+                return Err(HiveError::InvalidStack(point));
+            }
+        }
+        if !self.board.is_connected() {
+            return Err(HiveError::Disconnected);
+        }
+
+        for player in [Player::P1, Player::P2] {
+            let (queen, hand, full) = (
+                self.queen(player),
+                self.unplaced.hand(player),
+                full.hand(player),
+            );
+            let queens = self
+                .board
+                .iter()
+                .filter(|(_, stack)| stack.contains(&Piece::Queen(player)))
+                .map(|(&point, _)| point)
+                .collect_vec();
+            if queens != queen.into_iter().collect_vec() {
+                return Err(HiveError::QueenMismatch(player));
+            }
+
+            let owned = hand
+                .iter()
+                .chain(self.board.values().flat_map(|stack| stack.iter()))
+                .filter(|piece| piece.player() == player)
+                .map(Piece::kind)
+                .collect_vec();
+            for kind in PieceKind::ALL {
+                let expected = full.iter().filter(|piece| piece.kind() == kind).count();
+                if owned.iter().filter(|&&k| k == kind).count() != expected {
+                    return Err(HiveError::PieceCount(player, kind));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self) -> bool {
+        self.board.is_connected()
+            && match (self.rules.queen_by_turn, self.active) {
+                (Some(n), _) if self.turn + 1 > n => self.both_queens_placed(),
+                (Some(n), Player::P2) if self.turn + 1 == n => self.is_queen_placed(Player::P1),
+                _ => true,
+            }
+    }
+
+    fn queen(&self, player: Player) -> Option<Point> {
+        self.queens[player.index()]
+    }
+
+    fn set_queen(&mut self, player: Player, point: Option<Point>) {
+        self.queens[player.index()] = point;
+    }
+
+    /// Whether `player` has put every piece on the board, leaving them only movements.
+    pub fn hand_is_empty(&self, player: Player) -> bool {
+        self.unplaced.hand(player).is_empty()
+    }
+
+    pub fn is_queen_placed(&self, player: Player) -> bool {
+        self.queen(player).is_some()
+    }
+
+    pub fn both_queens_placed(&self) -> bool {
+        self.is_queen_placed(Player::P1) && self.is_queen_placed(Player::P2)
+    }
+
+    /// Swaps the colour of every piece, hand, and queen so the side to move becomes the other
+    /// player. The board geometry is unchanged.
+    pub fn flip_perspective(&self) -> State {
+        Self {
+            turn: self.turn,
+            active: !self.active,
+            queens: [self.queens[1], self.queens[0]],
+            unplaced: self.unplaced.flipped(),
+            board: Board {
+                map: self
+                    .board
+                    .iter()
+                    .map(|(&point, stack)| (point, stack.flipped()))
+                    .collect(),
+            },
+            rules: self.rules,
+            placed: PlacementOrder(
+                self.placed
+                    .0
+                    .iter()
+                    .map(|&(piece, point, height)| (piece.flipped(), point, height))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Weighted value of the pieces each player has on the board, P1's minus P2's.
+    pub fn material_balance(&self, weights: &PieceWeights) -> i32 {
+        self.board
+            .values()
+            .flat_map(|stack| stack.iter())
+            .map(|piece| match piece.player() {
+                Player::P1 => weights.weight(piece.kind()),
+                Player::P2 => -weights.weight(piece.kind()),
+            })
+            .sum()
+    }
+
+    /// A compact encoding of the position, shared by any two states that differ only by where
+    /// the hive sits on the grid. `rules` are not included. The bytes are:
+    ///
+    /// - the active player, `0` for P1 and `1` for P2
+    /// - `turn` as an unsigned LEB128 varint
+    /// - how many of each kind P1 has in hand, one byte per kind in [`PieceKind::ALL`] order,
+    ///   then the same for P2
+    /// - for every occupied hex, in ascending order: its axial coordinates relative to the
+    ///   corner of the board's bounding box as two varints, the stack height, and one byte per
+    ///   piece from the bottom up, `player << 4 | kind`
+    pub fn key(&self) -> Vec<u8> {
+        self.encode(Some(self.turn))
+    }
+
+    /// [`State::key`] without `turn`, so that the same position reached on different turns gets
+    /// the same key, as repetition rules count it.
+    pub fn position_key(&self) -> Vec<u8> {
+        self.encode(None)
+    }
+
+    fn encode(&self, turn: Option<usize>) -> Vec<u8> {
+        fn push_varint(key: &mut Vec<u8>, mut n: usize) {
+            while n >= 0x80 {
+                key.push(n as u8 | 0x80);
+                n >>= 7;
+            }
+            key.push(n as u8);
+        }
+
+        let mut key = vec![self.active.index() as u8];
+        if let Some(turn) = turn {
+            push_varint(&mut key, turn);
+        }
+        for hand in [&self.unplaced.p1, &self.unplaced.p2] {
+            key.extend(
+                PieceKind::ALL
+                    .iter()
+                    .map(|&kind| hand.iter().filter(|piece| piece.kind() == kind).count() as u8),
+            );
+        }
+
+        let min_q = self.board.keys().map(|p| p.axial().0).min().unwrap_or(0);
+        let min_r = self.board.keys().map(|p| p.axial().1).min().unwrap_or(0);
+        for ((q, r), stack) in self
+            .board
+            .iter()
+            .map(|(p, stack)| {
+                let (q, r) = p.axial();
+                ((q - min_q, r - min_r), stack)
+            })
+            .sorted_by_key(|(axial, _)| *axial)
+        {
+            push_varint(&mut key, q as usize);
+            push_varint(&mut key, r as usize);
+            key.push(stack.height() as u8);
+            key.extend(
+                stack
+                    .iter()
+                    .map(|piece| (piece.player().index() as u8) << 4 | piece.kind() as u8),
+            );
+        }
+        key
+    }
+
+    /// A single line describing the position, like `w 3 Q1B2A3G3S2 B2A3G3S2 0,0,0:wQ;0,0,1:bQwB`.
+    /// The space-separated fields are the side to move, `turn`, each player's hand as a count per
+    /// piece letter, and every occupied hex as `x,y,z:` followed by its stack from the bottom up.
+    /// An empty hand or board is written as `-`. `rules` are not included.
+    pub fn to_position_string(&self) -> String {
+        let hand = |pieces: &[Piece]| {
+            let hand = PieceKind::ALL
+                .iter()
+                .filter_map(|&kind| {
+                    let count = pieces.iter().filter(|piece| piece.kind() == kind).count();
+                    (count > 0).then(|| format!("{}{count}", kind.ascii()))
+                })
+                .join("");
+            if hand.is_empty() {
+                "-".to_string()
+            } else {
+                hand
+            }
+        };
+        let cells = self
+            .board
+            .iter()
+            .sorted_by_key(|(point, _)| **point)
+            .map(|(point, stack)| {
+                let pieces = stack.iter().map(|piece| piece.ascii()).join("");
+                format!("{},{},{}:{pieces}", point.x, point.y, point.z)
+            })
+            .join(";");
+        format!(
+            "{} {} {} {} {}",
+            self.active.letter(),
+            self.turn,
+            hand(&self.unplaced.p1),
+            hand(&self.unplaced.p2),
+            if cells.is_empty() { "-" } else { &cells },
+        )
+    }
+
+    /// Parses the output of [`State::to_position_string`]. Queens are found on the board, and
+    /// the position gets the default rules.
+    pub fn from_position_string(s: &str) -> Result<State, HiveError> {
+        fn hand(s: &str, player: Player) -> Option<Vec<Piece>> {
+            if s == "-" {
+                return Some(Vec::new());
+            }
+            let mut pieces = Vec::new();
+            let mut chars = s.chars().peekable();
+            while let Some(letter) = chars.next() {
+                let kind = PieceKind::from_letter(letter)?;
+                let count = chars
+                    .peeking_take_while(|c| c.is_ascii_digit())
+                    .collect::<String>()
+                    .parse()
+                    .ok()?;
+                pieces.extend(vec![Piece::new(kind, player); count]);
+            }
+            pieces.sort();
+            Some(pieces)
+        }
+
+        fn cell(s: &str) -> Option<(Point, Vec<Piece>)> {
+            let (coords, pieces) = s.split_once(':')?;
+            let (x, y, z) = coords.split(',').map(|n| n.parse().ok()).collect_tuple()?;
+            if pieces.is_empty() || pieces.len() % 2 != 0 {
+                return None;
+            }
+            let pieces = pieces
+                .chars()
+                .tuples()
+                .map(|(player, kind)| {
+                    Some(Piece::new(
+                        PieceKind::from_letter(kind)?,
+                        Player::from_letter(player)?,
+                    ))
+                })
+                .collect::<Option<_>>()?;
+            Some((Point::new(x?, y?, z?), pieces))
+        }
+
+        let invalid = || HiveError::InvalidNotation(s.to_string());
+        let (active, turn, p1, p2, cells) =
+            s.split_whitespace().collect_tuple().ok_or_else(invalid)?;
+        let active = active
+            .chars()
+            .exactly_one()
+            .ok()
+            .and_then(Player::from_letter)
+            .ok_or_else(invalid)?;
+        let turn = turn.parse().map_err(|_| invalid())?;
+        let unplaced = Pieces {
+            p1: hand(p1, Player::P1).ok_or_else(invalid)?,
+            p2: hand(p2, Player::P2).ok_or_else(invalid)?,
+        };
+        let mut board = Board::default();
+        if cells != "-" {
+            for (point, pieces) in cells
+                .split(';')
+                .map(cell)
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(invalid)?
+            {
+                if board.contains_key(&point) {
+                    return Err(invalid());
+                }
+                pieces
+                    .into_iter()
+                    .for_each(|piece| board.place(point, piece));
+            }
+        }
+
+        let queen = |player| {
+            board
+                .iter()
+                .find(|(_, stack)| stack.contains(&Piece::Queen(player)))
+                .map(|(&point, _)| point)
+        };
+        let (p1_queen, p2_queen) = (queen(Player::P1), queen(Player::P2));
+        Ok(State::new(
+            Some(turn),
+            active,
+            p1_queen,
+            p2_queen,
+            unplaced,
+            board,
+        ))
+    }
+
+    /// The representative of every position that matches this one up to translating, rotating,
+    /// or reflecting the hive, so equivalent positions have equal canonical states.
+    pub fn canonical(&self) -> State {
+        self.canonical_symmetry().0
+    }
+
+    /// [`State::canonical`], along with the symmetry that takes this position's points to it.
+    pub(crate) fn canonical_symmetry(&self) -> (State, Symmetry) {
+        (0..6)
+            .cartesian_product([false, true])
+            .map(|(rotations, reflect)| {
+                let symmetry = Symmetry {
+                    rotations,
+                    reflect,
+                    offset: Point::new(0, 0, 0),
+                };
+                let (state, offset) = self.transformed(|p| symmetry.apply(p));
+                (state, Symmetry { offset, ..symmetry })
+            })
+            .min_by_key(|(state, _)| state.key())
+            .unwrap()
+    }
+
+    /// Moves every point through `f`, then translates the hive so its bounding box starts at the
+    /// origin in axial coordinates. Also returns the translation.
+    fn transformed(&self, f: impl Fn(Point) -> Point) -> (State, Point) {
+        let board = Board {
+            map: self
+                .board
+                .iter()
+                .map(|(&p, stack)| (f(p), stack.clone()))
+                .collect(),
+        };
+        let offset = board.origin_offset();
+        let state = Self {
+            queens: self.queens.map(|queen| queen.map(|p| f(p) + offset)),
+            board: board.translated(offset),
+            unplaced: self.unplaced.clone(),
+            placed: PlacementOrder(
+                self.placed
+                    .0
+                    .iter()
+                    .map(|&(piece, p, height)| (piece, f(p) + offset, height))
+                    .collect(),
+            ),
+            ..*self
+        };
+        (state, offset)
+    }
+
+    /// How many empty hexes border `player`'s queen, or `None` if she hasn't been placed. A hex
+    /// counts as filled whatever is stacked on it, and a beetle on top of the queen herself
+    /// doesn't change anything.
+    pub fn liberties(&self, player: Player) -> Option<usize> {
+        self.distance_to_loss(player)
+    }
+
+    /// How many more of the hexes around `player`'s queen have to be filled for them to lose:
+    /// `Some(0)` if she's already surrounded, and `None` if she hasn't been placed.
+    pub fn distance_to_loss(&self, player: Player) -> Option<usize> {
+        Some(6 - self.count_surrounding(self.queen(player)?))
+    }
+
+    /// The top piece on each hex around `player`'s queen, indexed by [`Direction`], or `None` if
+    /// she hasn't been placed.
+    pub fn queen_ring(&self, player: Player) -> Option<[Option<Piece>; 6]> {
+        let queen = self.queen(player)?;
+        Some(
+            queen
+                .neighbors_with_direction()
+                .map(|(p, _)| self.board.top(&p).copied()),
+        )
+    }
+
+    pub fn count_surrounding(&self, point: Point) -> usize {
+        self.board.count_surrounding(point)
+    }
+
+    /// The opponent's replies that would cut the side to move's queen down to at most one
+    /// liberty, as if the side to move passed first. Sorted like [`State::legal_moves`], and
+    /// empty if that queen hasn't been placed.
+    pub fn threat_moves_next_turn(&self) -> Vec<Move> {
+        let player = self.active;
+        let Some(before) = self.liberties(player) else {
+            return Vec::new();
+        };
+        self.clone()
+            .with_active(!player)
+            .successors()
+            .into_iter()
+            .filter(|(_, next)| {
+                next.liberties(player)
+                    .is_some_and(|after| after <= 1 && after < before)
+            })
+            .map(|(mv, _)| mv)
+            .sorted()
+            .collect_vec()
+    }
+
+    /// The side to move's legal moves that make their queen safer: ones that free up hexes
+    /// around her, or that keep her liberties and leave the opponent able to reach fewer of
+    /// them. Sorted by liberties gained, then by how many of her free hexes are cut off from the
+    /// opponent.
+    pub fn queen_defending_moves(&self) -> Vec<Move> {
+        let player = self.active;
+        let Some(before) = self.liberties(player) else {
+            return Vec::new();
+        };
+        let exposed = |state: &State| {
+            let Some(queen) = state.queen(player) else {
+                return 0;
+            };
+            let free = state.board.empty_neighbors(queen).collect::<HashSet<_>>();
+            state
+                .clone()
+                .with_active(!player)
+                .successors()
+                .into_iter()
+                .filter_map(|(mv, _)| mv.destination())
+                .filter(|to| free.contains(to))
+                .distinct()
+                .count() as isize
+        };
+        let exposed_before = exposed(self);
+        self.successors()
+            .into_iter()
+            .filter_map(|(mv, next)| {
+                let gained = next.liberties(player)? as isize - before as isize;
+                let blocked = exposed_before - exposed(&next);
+                (gained > 0 || gained == 0 && blocked > 0).then_some((mv, gained, blocked))
+            })
+            .sorted_by_key(|&(mv, gained, blocked)| (Reverse(gained), Reverse(blocked), mv))
+            .map(|(mv, _, _)| mv)
+            .collect_vec()
+    }
+
+    /// Whether `player`'s queen is at most one move from being surrounded.
+    pub fn queen_in_danger(&self, player: Player) -> bool {
+        self.liberties(player)
+            .is_some_and(|liberties| liberties <= 1)
+    }
+
+    /// Whether both queens are on the board with all six hexes around each of them filled. The
+    /// move that closes the last gap may close both rings at once, which is a draw.
+    pub fn all_queens_surrounded(&self) -> bool {
+        self.is_surrounded(Player::P1) && self.is_surrounded(Player::P2)
+    }
+
+    fn is_surrounded(&self, player: Player) -> bool {
+        self.liberties(player) == Some(0)
+    }
+
+    pub fn result(&self) -> GameResult {
+        match (
+            self.is_surrounded(Player::P1),
+            self.is_surrounded(Player::P2),
+        ) {
+            (true, true) => GameResult::Draw,
+            (true, false) => GameResult::P2Wins,
+            (false, true) => GameResult::P1Wins,
+            (false, false) if self.rules.max_turns.is_some_and(|max| self.turn > max) => {
+                GameResult::Draw
+            }
+            (false, false) => GameResult::InProgress,
+        }
+    }
+
+    pub fn apply(&self, mv: Move) -> Result<State, HiveError> {
+        self.successors()
+            .into_iter()
+            .find(|(m, _)| *m == mv)
+            .map(|(_, state)| state)
+            .ok_or(HiveError::IllegalMove(mv))
+    }
+
+    /// Places a piece of `kind` from the hand of the side to move at `point`, like applying
+    /// [`Move::Place`] but saying why the placement is illegal.
+    pub fn place(&self, kind: PieceKind, point: Point) -> Result<State, HiveError> {
+        let point = point.canonicalize();
+        if !self.unplaced.kinds(self.active).contains(&kind) {
+            return Err(HiveError::NotInHand(self.active, kind));
+        }
+        if self.board.contains_key(&point) {
+            return Err(HiveError::Occupied(point));
+        }
+        if !self.placeable_points().contains(&point) {
+            return Err(HiveError::NotPlaceable(point));
+        }
+        // with the piece and point both fine, only queen timing can rule the placement out
+        self.apply(Move::Place { kind, to: point })
+            .map_err(|_| HiveError::QueenRequired(self.active))
+    }
+
+    /// Moves the active player's piece on top at `from` to `to`, like applying
+    /// [`Move::Relocate`] but saying why the move is illegal.
+    pub fn move_piece(&self, from: Point, to: Point) -> Result<State, HiveError> {
+        let (from, to) = (from.canonicalize(), to.canonicalize());
+        let stack = self.board.get(&from).ok_or(HiveError::Empty(from))?;
+        let piece = stack.top();
+        if piece.player() != self.active {
+            return Err(if stack.iter().any(|p| p.player() == self.active) {
+                HiveError::Pinned(from)
+            } else {
+                HiveError::WrongPlayer(piece.player())
+            });
+        }
+        if self.board.would_break_hive(from) {
+            return Err(HiveError::Pinned(from));
+        }
+        let mv = Move::Relocate {
+            kind: piece.kind(),
+            from,
+            to,
+        };
+        self.relocations(from)
+            .into_iter()
+            .find(|(m, _)| *m == mv)
+            .map(|(_, state)| state)
+            .ok_or(HiveError::IllegalMove(mv))
+    }
+
+    /// Like [`State::apply`], but also reports how the move changed both queens' liberties.
+    pub fn apply_annotated(&self, mv: Move) -> Result<(State, LibertyDelta), HiveError> {
+        let next = self.apply(mv)?;
+        let delta = LibertyDelta {
+            p1_before: self.liberties(Player::P1),
+            p1_after: next.liberties(Player::P1),
+            p2_before: self.liberties(Player::P2),
+            p2_after: next.liberties(Player::P2),
+        };
+        Ok((next, delta))
+    }
+
+    #[cfg(feature = "std")]
+    pub fn apply_uhp(&self, mv: &str) -> Result<State, HiveError> {
+        self.apply(Move::from_uhp(mv, self)?)
+    }
+
+    /// Whether `mv` completes the ring around the mover's own queen while leaving the
+    /// opponent's queen free, i.e. hands the opponent the win.
+    pub fn loses_immediately(&self, mv: &Move) -> bool {
+        let loss = match self.active {
+            Player::P1 => GameResult::P2Wins,
+            Player::P2 => GameResult::P1Wins,
+        };
+        self.apply(*mv).is_ok_and(|state| state.result() == loss)
+    }
+
+    pub fn get_moves(&self) -> HashSet<State> {
+        self.successors()
+            .into_iter()
+            .map(|(_, state)| state)
+            .collect()
+    }
+
+    /// Every legal move, sorted by [`Move`]'s ordering so the result doesn't depend on hash
+    /// iteration order. Placements come before relocations, which come before passing.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        self.successors()
+            .into_iter()
+            .map(|(mv, _)| mv)
+            .sorted()
+            .collect_vec()
+    }
+
+    /// A uniformly random legal move. There is always at least one, since a player with nothing
+    /// else to do passes.
+    pub fn random_move<R: Rng + ?Sized>(&self, rng: &mut R) -> Move {
+        self.random_successor(rng).0
+    }
+
+    fn random_successor<R: Rng + ?Sized>(&self, rng: &mut R) -> (Move, State) {
+        let mut successors = self.successors();
+        successors.swap_remove(rng.random_range(..successors.len()))
+    }
+
+    /// Plays random moves until the game is decided, calling it a draw if it's still going after
+    /// `max_plies`.
+    pub fn random_playout<R: Rng + ?Sized>(&self, rng: &mut R, max_plies: usize) -> GameResult {
+        let mut state = self.clone();
+        for _ in 0..max_plies {
+            if state.result().is_over() {
+                break;
+            }
+            state = state.random_successor(rng).1;
+        }
+        match state.result() {
+            GameResult::InProgress => GameResult::Draw,
+            result => result,
+        }
+    }
+
+    pub fn perft(&self, depth: usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        self.successors()
+            .into_iter()
+            .map(|(_, state)| state.perft(depth - 1))
+            .sum()
+    }
+
+    /// Like [`State::perft`], but the root moves are counted in parallel.
+    #[cfg(feature = "rayon")]
+    pub fn perft_parallel(&self, depth: usize) -> u64 {
+        use rayon::prelude::*;
+
+        if depth == 0 {
+            return 1;
+        }
+        self.successors()
+            .into_par_iter()
+            .map(|(_, state)| state.perft(depth - 1))
+            .sum()
+    }
+
+    pub fn perft_divide(&self, depth: usize) -> Vec<(Move, u64)> {
+        if depth == 0 {
+            return Vec::new();
+        }
+        self.successors()
+            .into_iter()
+            .map(|(mv, state)| (mv, state.perft(depth - 1)))
+            .sorted()
+            .collect_vec()
+    }
+
+    /// Whether the side to move has nothing to place and nothing that can move, so their only
+    /// legal move is [`Move::Pass`].
+    pub fn pass_required(&self) -> bool {
+        self.placements().is_empty() && self.movements().is_empty()
+    }
+
+    fn successors(&self) -> Vec<(Move, State)> {
+        let mut moves = self
+            .placements()
+            .into_iter()
+            .chain(self.movements())
+            .collect_vec();
+        if moves.is_empty() {
+            moves.push((Move::Pass, self.next_turn(None, None, self.board.clone())));
+        }
+        moves
+    }
+
+    /// One placement per kind in hand and point, however many of that kind are left: identical
+    /// pieces are interchangeable, so which one goes down makes no difference.
+    fn placements(&self) -> Vec<(Move, State)> {
+        let hand = self.unplaced.hand(self.active);
+        self.unplaced
+            .kinds(self.active)
+            .map(|kind| {
+                let idx = hand.iter().position(|piece| piece.kind() == kind).unwrap();
+                (idx, kind)
+            })
+            .cartesian_product(self.placeable_points())
+            .map(|((idx, kind), point)| {
+                let mut b = self.board.clone();
+                let mut pieces = self.unplaced.clone();
+                let piece = pieces.remove(self.active, idx);
+                b.place(point, piece);
+                let mut next =
+                    self.next_turn((kind == PieceKind::Queen).then_some(point), Some(pieces), b);
+                next.placed.0.push((piece, point, 0));
+                (Move::Place { kind, to: point }, next)
+            })
+            .filter(|(_, state)| state.validate())
+            .collect_vec()
+    }
+
+    /// Relocations of the top piece of each of the active player's stacks. Anything underneath,
+    /// including a beetle with another beetle on it, stays put.
+    fn movements(&self) -> Vec<(Move, State)> {
+        self.board
+            .iter()
+            .filter(|&(_, stack)| stack.top().player() == self.active)
+            .flat_map(|(&point, _)| self.relocations(point))
+            .collect_vec()
+    }
+
+    /// Where `player` has a piece that can't move: either lifting it would split the hive, or
+    /// something is on top of it.
+    pub fn pins(&self, player: Player) -> HashSet<Point> {
+        let bridges = self.board.articulation_points();
+        self.board
+            .iter()
+            .filter(|(point, stack)| {
+                let (top, covered) = stack.split_last().unwrap();
+                covered.iter().any(|piece| piece.player() == player)
+                    || top.player() == player && covered.is_empty() && bridges.contains(*point)
+            })
+            .map(|(&point, _)| point)
+            .collect()
+    }
+
+    /// How many placements and relocations the side to move has, for judging how wide a search
+    /// from here will be.
+    pub fn branching_factor_stats(&self) -> BranchingFactorStats {
+        let per_piece = self
+            .board
+            .keys()
+            .map(|&point| self.relocations(point).len())
+            .collect_vec();
+        BranchingFactorStats {
+            placements: self.placements().len(),
+            relocations: per_piece.iter().sum(),
+            max_destinations: per_piece.into_iter().max().unwrap_or(0),
+        }
+    }
+
+    /// Legal moves, keeping just one of any group that lead to the same [`State::canonical`]
+    /// position.
+    pub fn canonical_moves(&self) -> Vec<Move> {
+        self.successors()
+            .into_iter()
+            .distinct_by(|(_, state)| state.canonical())
+            .map(|(mv, _)| mv)
+            .collect_vec()
+    }
+
+    /// Every position reachable in at most `depth` plies, this one included, as
+    /// [`State::canonical`] positions so symmetric lines are only explored once. Finished games
+    /// aren't played on.
+    pub fn all_reachable_positions(&self, depth: usize) -> HashSet<State> {
+        let mut seen = HashSet::from_iter([self.canonical()]);
+        let mut frontier = seen.iter().cloned().collect_vec();
+        for _ in 0..depth {
+            frontier = frontier
+                .iter()
+                .filter(|state| !state.result().is_over())
+                .flat_map(|state| state.get_moves())
+                .map(|state| state.canonical())
+                .filter(|state| seen.insert(state.clone()))
+                .collect_vec();
+        }
+        seen
+    }
+
+    pub fn legal_moves_count_by_kind(&self) -> HashMap<PieceKind, usize> {
+        self.successors()
+            .into_iter()
+            .filter_map(|(mv, _)| mv.piece_kind())
+            .fold(HashMap::new(), |mut counts, kind| {
+                *counts.entry(kind).or_default() += 1;
+                counts
+            })
+    }
+
+    pub fn legal_moves_for(&self, point: Point) -> Vec<Move> {
+        self.relocations(point)
+            .into_iter()
+            .map(|(mv, _)| mv)
+            .collect()
+    }
+
+    /// For every empty hex next to the hive, who could place or move a piece there if it were
+    /// their turn.
+    pub fn coverage_map(&self) -> HashMap<Point, Coverage> {
+        let mut map: HashMap<_, _> = self
+            .board
+            .keys()
+            .flat_map(|&point| self.board.empty_neighbors(point))
+            .map(|point| (point, Coverage::default()))
+            .collect();
+        for player in [Player::P1, Player::P2] {
+            for (mv, _) in self.clone().with_active(player).successors() {
+                let Some(coverage) = mv.destination().and_then(|to| map.get_mut(&to)) else {
+                    continue;
+                };
+                match (player, mv) {
+                    (Player::P1, Move::Place { .. }) => coverage.p1_place = true,
+                    (Player::P2, Move::Place { .. }) => coverage.p2_place = true,
+                    (Player::P1, _) => coverage.p1_move = true,
+                    (Player::P2, _) => coverage.p2_move = true,
+                }
+            }
+        }
+        map
+    }
+
+    /// Where the piece at `point` can move to.
+    pub fn legal_destinations(&self, point: Point) -> HashSet<Point> {
+        self.legal_moves_for(point)
+            .into_iter()
+            .filter_map(|mv| mv.destination())
+            .collect()
+    }
+
+    fn relocations(&self, point: Point) -> Vec<(Move, State)> {
+        let Some(piece) = self.board.top(&point) else {
+            return Vec::new();
+        };
+        if piece.player() != self.active || self.board.would_break_hive(point) {
+            return Vec::new();
+        }
+        let kind = piece.kind();
+        piece_moves(kind, point, &self.board)
+            .into_iter()
+            .filter(|&to| to != point)
+            .filter(|to| self.rules.climbing || !self.board.contains_key(to))
+            .distinct()
+            .map(|to| {
+                (
+                    Move::Relocate {
+                        kind,
+                        from: point,
+                        to,
+                    },
+                    self.relocate(point, to),
+                )
+            })
+            .filter(|(_, state)| state.validate())
+            .collect_vec()
+    }
+
+    /// Moves the top piece at `from` to `to`. Only a piece on top of its stack ever moves, so a
+    /// queen changes hexes exactly when she's the piece being moved, whichever side she's on.
+    /// Covering or uncovering a queen leaves her where she is.
+    fn relocate(&self, from: Point, to: Point) -> State {
+        let mut board = self.board.clone();
+        board.move_top(from, to);
+        let moved = *board[&to].top();
+        let mut next = self.next_turn(None, None, board);
+        if let Piece::Queen(owner) = moved {
+            next.set_queen(owner, Some(to));
+        }
+        let height = |point| self.board.get(&point).map_or(0, Stack::height);
+        next.placed
+            .moved((from, height(from) - 1), (to, height(to)));
+        next
+    }
+}
+
+fn piece_moves(kind: PieceKind, point: Point, board: &Board) -> Vec<Point> {
+    match kind {
+        PieceKind::Queen => queen_moves(point, board),
+        PieceKind::Beetle => beetle_moves(point, board),
+        PieceKind::Ant => ant_moves(point, board),
+        PieceKind::Grasshopper => grasshopper_moves(point, board),
+        PieceKind::Spider => spider_moves(point, board),
+        PieceKind::Mosquito => mosquito_moves(point, board),
+    }
+}
+
+fn queen_moves(point: Point, board: &Board) -> Vec<Point> {
+    point.slides(&board.without(point)).collect_vec()
+}
+
+/// A beetle steps to any neighbour that [`beetle_gate`] lets it through to, whether that's along
+/// the ground, up onto a stack, across the top of the hive, or back down.
+fn beetle_moves(point: Point, board: &Board) -> Vec<Point> {
+    point
+        .neighbors()
+        .into_iter()
+        .filter(|&to| beetle_gate(point, to, board))
+        .collect_vec()
+}
+
+/// Whether the top piece at `from` can step to the neighbouring hex `to` moving as a beetle. It
+/// has to squeeze between the stacks either side of the step, so it's blocked if both are taller
+/// than the stack it leaves (without it) and the one it lands on. A step along the ground is an
+/// ordinary slide, which also has to keep touching the hive.
+fn beetle_gate(from: Point, to: Point, board: &Board) -> bool {
+    let height = |p: &Point| board.get(p).map_or(0, |stack| stack.height());
+    let neighbors = from.neighbors();
+    let Some(i) = neighbors.iter().position(|&p| p == to) else {
+        return false;
+    };
+    let left = height(&neighbors[(i + 5) % 6]);
+    let right = height(&neighbors[(i + 1) % 6]);
+    match (height(&from).saturating_sub(1)).max(height(&to)) {
+        0 => (left > 0) != (right > 0),
+        top => left.min(right) <= top,
+    }
+}
+
+fn ant_moves(point: Point, board: &Board) -> Vec<Point> {
+    fn ant_moves(point: Point, board: &Board, visited: &mut HashSet<Point>) {
+        point
+            .slides(board)
+            .filter(|p| visited.insert(*p))
+            // HACK: consume the iterator so that visited isn't borrowed mutably more
+            // than once
+            .collect_vec()
+            .into_iter()
+            .for_each(|p| ant_moves(p, board, visited));
+    }
+
+    let mut visited = HashSet::new();
+    ant_moves(point, &board.without(point), &mut visited);
+    visited.into_iter().collect_vec()
+}
+
+/// A mosquito on the ground borrows the moves of every kind it touches, except other
+/// mosquitoes. Once it has climbed, it's stuck moving as a beetle until it comes back down.
+fn mosquito_moves(point: Point, board: &Board) -> Vec<Point> {
+    if board[&point].height() > 1 {
+        return beetle_moves(point, board);
+    }
+    point
+        .neighbors()
+        .into_iter()
+        .filter_map(|p| board.top(&p).map(Piece::kind))
+        .filter(|&kind| kind != PieceKind::Mosquito)
+        .distinct()
+        .flat_map(|kind| piece_moves(kind, point, board))
+        .collect_vec()
+}
+
+fn grasshopper_moves(point: Point, board: &Board) -> Vec<Point> {
+    grasshopper_jumps(point, board)
+        .into_iter()
+        .map(|(p, _)| p)
+        .collect_vec()
+}
+
+/// Every landing square for a grasshopper at `point`, along with the pieces it jumps over to get
+/// there. Directions without an adjacent piece are skipped since a grasshopper must jump at least
+/// one piece.
+pub fn grasshopper_jumps(point: Point, board: &Board) -> Vec<(Point, Vec<Point>)> {
+    point
+        .neighbors()
+        .into_iter()
+        .filter_map(|first| {
+            // step with canonical points: bumping one coordinate of a canonical point doesn't
+            // always give another canonical point, and the board is keyed by canonical ones
+            let step = first - point;
+            let mut jumped = Vec::new();
+            let mut p = first;
+            while board.contains_key(&p) {
+                jumped.push(p);
+                p = p + step;
+            }
+            (!jumped.is_empty()).then_some((p, jumped))
+        })
+        .collect_vec()
+}
+
+fn spider_moves(point: Point, board: &Board) -> Vec<Point> {
+    fn spider_moves(
+        point: Point,
+        board: &Board,
+        path: &mut Vec<Point>,
+        moves_remaining: usize,
+    ) -> Vec<Point> {
+        if moves_remaining == 0 {
+            return vec![point];
+        }
+        point
+            .slides(board)
+            .filter(|p| !path.contains(p))
+            .collect_vec()
+            .into_iter()
+            .flat_map(|p| {
+                path.push(p);
+                let ends = spider_moves(p, board, path, moves_remaining - 1);
+                path.pop();
+                ends
+            })
+            .collect_vec()
+    }
+
+    spider_moves(point, &board.without(point), &mut vec![point], 3)
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            turn: 0,
+            active: Player::P1,
+            queens: [None, None],
+            unplaced: Pieces::default(),
+            board: Board::default(),
+            rules: Rules::default(),
+            placed: PlacementOrder::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Game {
+    state: State,
+    history: Vec<(State, Move)>,
+}
+
+impl Game {
+    pub fn new(state: State) -> Self {
+        Self {
+            state,
+            history: Vec::new(),
+        }
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    /// Every move played so far, paired with the state it was played from.
+    pub fn history(&self) -> &[(State, Move)] {
+        &self.history
+    }
+
+    pub fn play(&mut self, mv: Move) -> Result<(), HiveError> {
+        let next = self.state.apply(mv)?;
+        let previous = core::mem::replace(&mut self.state, next);
+        self.history.push((previous, mv));
+        Ok(())
+    }
+
+    /// How many times the current position has come up in this game, including now. Positions
+    /// are compared by [`State::position_key`], so the turn number doesn't matter.
+    pub fn repetitions(&self) -> usize {
+        let key = self.state.position_key();
+        1 + self
+            .history
+            .iter()
+            .filter(|(state, _)| state.position_key() == key)
+            .count()
+    }
+
+    /// Takes back the last move, returning it, or `None` if no moves have been played.
+    pub fn undo(&mut self) -> Option<Move> {
+        let (state, mv) = self.history.pop()?;
+        self.state = state;
+        Some(mv)
+    }
+}
+
+/// Stand-ins for [`Itertools::unique`] and [`Itertools::unique_by`], which need `std`.
+trait Distinct: Iterator + Sized {
+    fn distinct_by<K: Eq + Hash>(
+        self,
+        mut key: impl FnMut(&Self::Item) -> K,
+    ) -> impl Iterator<Item = Self::Item> {
+        let mut seen = HashSet::new();
+        self.filter(move |item| seen.insert(key(item)))
+    }
+
+    fn distinct(self) -> impl Iterator<Item = Self::Item>
+    where
+        Self::Item: Clone + Eq + Hash,
+    {
+        self.distinct_by(Self::Item::clone)
+    }
+}
+
+impl<I: Iterator> Distinct for I {}
+
+#[cfg(test)]
+mod tests {
+    use std::iter::once;
+
+    use rand::{SeedableRng, rngs::SmallRng};
+
+    use super::*;
+
+    #[test]
+    fn test_first_move() {
+        let state = State::default();
+
+        let moves = state.get_moves();
+
+        assert_eq!(moves.len(), 5);
+    }
+
+    #[test]
+    fn test_second_move() {
+        for state in State::default().get_moves().into_iter() {
+            let moves = state.get_moves();
+
+            assert_eq!(moves.len(), 5 * 6);
+        }
+    }
+
+    #[test]
+    fn test_first_reply_adjacent() {
+        let opening = State::empty_board_opening();
+        assert_eq!(opening, State::default());
+
+        for (first, state) in opening.successors() {
+            let Move::Place { to: first, .. } = first else {
+                panic!("{first:?}");
+            };
+            let replies = state.successors();
+
+            assert!(replies.iter().all(|(mv, _)| {
+                mv.is_placement() && mv.destination().is_some_and(|to| to.distance(&first) == 1)
+            }));
+            let sides = replies
+                .iter()
+                .filter_map(|(mv, _)| mv.destination())
+                .distinct()
+                .collect::<HashSet<_>>();
+            assert_eq!(sides, HashSet::from_iter(first.neighbors()));
+            // every side is as good as any other
+            let canonical = state.canonical_moves();
+            assert_eq!(canonical.len(), 5);
+            assert!(canonical.iter().map(|mv| mv.piece_kind()).all_unique());
+        }
+    }
+
+    #[test]
+    fn test_second_move_off_origin() {
+        let first = Point::new(3, -1, 2);
+        let state = State::default()
+            .with_board(Board::from([(first, Piece::Spider(Player::P1))]))
+            .with_active(Player::P2);
+
+        let points = state.placeable_points();
+
+        assert_eq!(
+            points.iter().copied().collect::<HashSet<_>>(),
+            HashSet::from_iter(first.neighbors())
+        );
+        assert_eq!(points.len(), 6);
+        assert!(
+            state
+                .successors()
+                .iter()
+                .all(|(mv, _)| mv.destination().is_some_and(|to| to.distance(&first) == 1))
+        );
+    }
+
+    #[test]
+    fn test_legal_moves_stable() {
+        let board = Board::from([
+            (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+            (Point::new(0, 0, 1), Piece::Queen(Player::P2)),
+            (Point::new(0, -1, 0), Piece::Ant(Player::P1)),
+            (Point::new(0, 1, 1), Piece::Beetle(Player::P2)),
+        ]);
+        let state = State::default()
+            .with_turn(2)
+            .with_queens(Some(Point::new(0, 0, 0)), Some(Point::new(0, 0, 1)))
+            .with_board(board.clone());
+        let rebuilt = State::default()
+            .with_turn(2)
+            .with_queens(Some(Point::new(0, 0, 0)), Some(Point::new(0, 0, 1)))
+            .with_board(board.iter().map(|(&p, s)| (p, *s.top())).collect());
+
+        let moves = state.legal_moves();
+
+        assert_eq!(moves, state.legal_moves());
+        assert_eq!(moves, rebuilt.legal_moves());
+        assert!(moves.is_sorted());
+        assert_eq!(moves.len(), state.successors().len());
+        assert!(moves.iter().all(|&mv| state.apply(mv).is_ok()));
+    }
+
+    #[test]
+    fn test_validate_no_queen() {
+        let state = State {
+            turn: 5,
+            active: Player::P2,
+            ..Default::default()
+        };
+
+        assert!(!state.validate());
+    }
+
+    #[test]
+    fn test_validate_valid() {
+        let state = State::default();
+
+        assert!(state.validate());
+    }
+
+    #[test]
+    fn test_queens_placed() {
+        let state = State::default();
+        assert!(!state.is_queen_placed(Player::P1));
+        assert!(!state.both_queens_placed());
+
+        let state = state
+            .apply(Move::Place {
+                kind: PieceKind::Queen,
+                to: Point::new(0, 0, 0),
+            })
+            .unwrap();
+        assert!(state.is_queen_placed(Player::P1));
+        assert!(!state.is_queen_placed(Player::P2));
+        assert!(!state.both_queens_placed());
+
+        let state = state
+            .apply(Move::Place {
+                kind: PieceKind::Queen,
+                to: Point::new(0, 0, 1),
+            })
+            .unwrap();
+        assert!(state.both_queens_placed());
+    }
+
+    #[test]
+    fn test_queen_by_turn() {
+        let forced_at = |queen_by_turn| {
+            let mut state = State::default().with_rules(Rules {
+                queen_by_turn,
+                ..Default::default()
+            });
+            loop {
+                let mut moves = state
+                    .successors()
+                    .into_iter()
+                    .filter(|(mv, _)| mv.piece_kind() != Some(PieceKind::Queen));
+                match moves.next() {
+                    Some((_, next)) => state = next,
+                    None => return (state.turn, state.active),
+                }
+            }
+        };
+
+        // `turn` counts from zero
+        assert_eq!(forced_at(Some(4)), (3, Player::P1));
+        assert_eq!(forced_at(Some(3)), (2, Player::P1));
+        assert!(
+            State {
+                turn: 9,
+                active: Player::P2,
+                ..Default::default()
+            }
+            .with_rules(Rules {
+                queen_by_turn: None,
+                ..Default::default()
+            })
+            .validate()
+        );
+    }
+
+    #[test]
+    fn test_legal_moves_for_ant() {
+        let state = State {
+            queens: [Some(Point::new(0, 0, 0)), Some(Point::new(1, 0, 0))],
+            board: Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+                (Point::new(-1, 0, 0), Piece::Ant(Player::P1)),
+            ]),
+            ..Default::default()
+        };
+
+        let moves = state.legal_moves_for(Point::new(-1, 0, 0));
+
+        assert_eq!(moves.len(), 7);
+        assert!(moves.iter().all(|mv| matches!(
+            mv,
+            Move::Relocate {
+                kind: PieceKind::Ant,
+                from,
+                ..
+            } if *from == Point::new(-1, 0, 0)
+        )));
+    }
+
+    #[test]
+    fn test_legal_destinations() {
+        let state = State {
+            queens: [Some(Point::new(0, 0, 0)), Some(Point::new(1, 0, 0))],
+            board: Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+                (Point::new(-1, 0, 0), Piece::Ant(Player::P1)),
+                (Point::new(2, 0, 0), Piece::Grasshopper(Player::P1)),
+            ]),
+            ..Default::default()
+        };
+
+        let ant = state.legal_destinations(Point::new(-1, 0, 0));
+        assert_eq!(ant.len(), 9);
+        assert!(!ant.contains(&Point::new(-1, 0, 0)));
+        assert!(ant.iter().all(|p| !state.board.contains_key(p)));
+        assert_eq!(
+            state.legal_destinations(Point::new(2, 0, 0)),
+            HashSet::from([Point::new(-2, 0, 0)])
+        );
+        assert!(state.legal_destinations(Point::new(5, 0, 0)).is_empty());
+    }
+
+    #[test]
+    fn test_spider_moves() {
+        let state = State {
+            queens: [Some(Point::new(0, 0, 0)), Some(Point::new(1, 0, 0))],
+            board: Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+                (Point::new(-1, 0, 0), Piece::Spider(Player::P1)),
+            ]),
+            ..Default::default()
+        };
+
+        // leaning on its own hex, the spider could wander off into empty space
+        assert_eq!(
+            state.legal_destinations(Point::new(-1, 0, 0)),
+            HashSet::from([Point::new(1, 1, 0), Point::new(1, 0, -1)])
+        );
+    }
+
+    #[test]
+    fn test_queen_keeps_contact() {
+        let state = State {
+            queens: [Some(Point::new(0, 0, 0)), Some(Point::new(0, 1, 0))],
+            board: Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(0, 1, 0), Piece::Queen(Player::P2)),
+                (Point::new(0, 1, 1), Piece::Ant(Player::P2)),
+                (Point::new(0, 0, 2), Piece::Ant(Player::P1)),
+                (Point::new(-1, 0, 1), Piece::Ant(Player::P1)),
+            ]),
+            ..Default::default()
+        };
+
+        // (-1, 0, 0) touches the hive, but the queen would lose contact sliding there
+        assert_eq!(
+            state.legal_destinations(Point::new(0, 0, 0)),
+            HashSet::from([Point::new(0, 0, 1), Point::new(1, 0, 0)])
+        );
+    }
+
+    #[test]
+    fn test_branching_factor_stats() {
+        let state = State {
+            queens: [Some(Point::new(0, 0, 0)), Some(Point::new(1, 0, 0))],
+            unplaced: Pieces::from_counts(&HashMap::from([(PieceKind::Ant, 1)]), Player::P1),
+            board: Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+                (Point::new(-1, 0, 0), Piece::Ant(Player::P1)),
+                (Point::new(2, 0, 0), Piece::Grasshopper(Player::P1)),
+            ]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            state.branching_factor_stats(),
+            BranchingFactorStats {
+                placements: 8,
+                relocations: 10,
+                max_destinations: 9,
+            }
+        );
+        assert_eq!(
+            State::default().branching_factor_stats(),
+            BranchingFactorStats {
+                placements: 5,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_mosquito_moves() {
+        let state = |mosquito_on_spider| {
+            let mut board = Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+                (Point::new(-1, 0, 1), Piece::Ant(Player::P1)),
+            ]);
+            if mosquito_on_spider {
+                board.place(Point::new(-1, 0, 0), Piece::Spider(Player::P2));
+            }
+            board.place(Point::new(-1, 0, 0), Piece::Mosquito(Player::P1));
+            State {
+                queens: [Some(Point::new(0, 0, 0)), Some(Point::new(1, 0, 0))],
+                board,
+                ..Default::default()
+            }
+        };
+
+        // on top of the spider it can only move as a beetle
+        assert_eq!(
+            state(true).legal_destinations(Point::new(-1, 0, 0)),
+            Point::new(-1, 0, 0).neighbors().into_iter().collect()
+        );
+
+        // on the ground it borrows the ant's moves, which cover the queen's
+        let mut ant = state(false);
+        ant.board.remove(&Point::new(-1, 0, 0));
+        ant.board
+            .place(Point::new(-1, 0, 0), Piece::Ant(Player::P1));
+        assert_eq!(
+            state(false).legal_destinations(Point::new(-1, 0, 0)),
+            ant.legal_destinations(Point::new(-1, 0, 0))
+        );
+    }
+
+    #[test]
+    fn test_legal_moves_for_pinned() {
+        let state = State {
+            queens: [Some(Point::new(0, 0, 0)), Some(Point::new(2, 0, 0))],
+            board: Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(1, 0, 0), Piece::Ant(Player::P1)),
+                (Point::new(2, 0, 0), Piece::Queen(Player::P2)),
+            ]),
+            ..Default::default()
+        };
+
+        assert!(state.legal_moves_for(Point::new(1, 0, 0)).is_empty());
+        assert!(!state.legal_moves_for(Point::new(0, 0, 0)).is_empty());
+    }
+
+    #[test]
+    fn test_custom_counts() {
+        let counts = HashMap::from([(PieceKind::Ant, 2)]);
+        let state = State::new(
+            None,
+            Player::P1,
+            None,
+            None,
+            Pieces::from_counts(&counts, Player::P1).with_counts(&counts, Player::P2),
+            Board::default(),
+        );
+
+        assert_eq!(state.unplaced.p1, vec![Piece::Ant(Player::P1); 2]);
+        assert_eq!(state.get_moves().len(), 1);
+    }
+
+    #[test]
+    fn test_pass_required() {
+        // P1's queen holds the hive together and there's nothing left in her hand
+        let state = State::default()
+            .with_turn(5)
+            .with_queens(Some(Point::new(0, 0, 0)), Some(Point::new(1, 0, 0)))
+            .with_unplaced(Pieces::from_counts(&HashMap::new(), Player::P1))
+            .with_board(Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+                (Point::new(-1, 0, 0), Piece::Ant(Player::P2)),
+            ]));
+
+        assert!(state.pass_required());
+        assert_eq!(state.legal_moves(), vec![Move::Pass]);
+
+        let state = state.apply(Move::Pass).unwrap();
+        assert!(!state.pass_required());
+        assert!(!state.legal_moves().contains(&Move::Pass));
+        assert!(!State::default().pass_required());
+    }
+
+    /// A position with both queens down and nothing left in P1's hand, so P1's legal moves are
+    /// all relocations.
+    fn snapshot(board: Board, p1_queen: Point, p2_queen: Point) -> State {
+        State::default()
+            .with_turn(6)
+            .with_queens(Some(p1_queen), Some(p2_queen))
+            .with_unplaced(Pieces::from_counts(&HashMap::new(), Player::P1))
+            .with_board(board)
+    }
+
+    fn relocations(kind: PieceKind, from: Point, to: &[(isize, isize, isize)]) -> Vec<Move> {
+        to.iter()
+            .map(|&(x, y, z)| Move::Relocate {
+                kind,
+                from,
+                to: Point::new(x, y, z),
+            })
+            .collect_vec()
+    }
+
+    #[test]
+    fn test_snapshot_ant_on_ring() {
+        // P2's ring around an empty hex, with P1's queen and ant hanging off the east side
+        let origin = Point::new(0, 0, 0);
+        let [w, sw, se, e, ne, nw] = origin.neighbors()[..] else {
+            unreachable!()
+        };
+        let ant = Point::new(3, 0, 0);
+        let state = snapshot(
+            Board::from([
+                (e, Piece::Queen(Player::P2)),
+                (ne, Piece::Spider(Player::P2)),
+                (nw, Piece::Spider(Player::P2)),
+                (w, Piece::Grasshopper(Player::P2)),
+                (sw, Piece::Grasshopper(Player::P2)),
+                (se, Piece::Beetle(Player::P2)),
+                (Point::new(2, 0, 0), Piece::Queen(Player::P1)),
+                (ant, Piece::Ant(Player::P1)),
+            ]),
+            Point::new(2, 0, 0),
+            e,
+        );
+
+        assert_eq!(
+            state.legal_moves(),
+            relocations(
+                PieceKind::Ant,
+                ant,
+                &[
+                    (-2, 0, 0),
+                    (-1, -1, 0),
+                    (-1, 0, 1),
+                    (0, -2, 0),
+                    (0, -1, -1),
+                    (0, 0, -2),
+                    (0, 0, 2),
+                    (0, 1, 1),
+                    (0, 2, 0),
+                    (1, 0, -1),
+                    (1, 1, 0),
+                    (2, 0, -1),
+                    (2, 1, 0),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_snapshot_spider_in_corner() {
+        let origin = Point::new(0, 0, 0);
+        let [w, _, _, e, ne, _] = origin.neighbors()[..] else {
+            unreachable!()
+        };
+        let state = snapshot(
+            Board::from([
+                (origin, Piece::Queen(Player::P2)),
+                (e, Piece::Grasshopper(Player::P2)),
+                (ne, Piece::Spider(Player::P1)),
+                (w, Piece::Queen(Player::P1)),
+                (Point::new(-2, 0, 0), Piece::Ant(Player::P2)),
+            ]),
+            w,
+            origin,
+        );
+
+        assert_eq!(
+            state.legal_moves(),
+            relocations(PieceKind::Spider, ne, &[(-2, 0, 1), (1, 0, -1)])
+        );
+    }
+
+    #[test]
+    fn test_snapshot_grasshopper_jumps_two() {
+        let origin = Point::new(0, 0, 0);
+        let [w, _, _, e, _, _] = origin.neighbors()[..] else {
+            unreachable!()
+        };
+        let state = snapshot(
+            Board::from([
+                (w, Piece::Grasshopper(Player::P1)),
+                (origin, Piece::Queen(Player::P2)),
+                (e, Piece::Spider(Player::P2)),
+                (Point::new(1, 0, 1), Piece::Queen(Player::P1)),
+                (Point::new(1, 0, 2), Piece::Ant(Player::P2)),
+            ]),
+            Point::new(1, 0, 1),
+            origin,
+        );
+
+        assert_eq!(
+            state.legal_moves(),
+            relocations(PieceKind::Grasshopper, w, &[(2, 0, 0)])
+        );
+    }
+
+    #[test]
+    fn test_snapshot_boxed_in_beetle() {
+        // the beetle can climb onto anything around it, but can't squeeze east between the two
+        // stacks
+        let origin = Point::new(0, 0, 0);
+        let [w, sw, se, _, ne, nw] = origin.neighbors()[..] else {
+            unreachable!()
+        };
+        let state = snapshot(
+            Board::from([
+                (origin, Piece::Beetle(Player::P1)),
+                (w, Piece::Queen(Player::P1)),
+                (Point::new(-2, 0, 0), Piece::Spider(Player::P2)),
+                (sw, Piece::Queen(Player::P2)),
+                (nw, Piece::Grasshopper(Player::P2)),
+                (se, Piece::Ant(Player::P2)),
+                (se, Piece::Beetle(Player::P2)),
+                (ne, Piece::Ant(Player::P2)),
+                (ne, Piece::Beetle(Player::P2)),
+            ]),
+            w,
+            sw,
+        );
+
+        assert_eq!(
+            state.legal_moves(),
+            relocations(
+                PieceKind::Beetle,
+                origin,
+                &[(-1, 0, 0), (0, -1, 0), (0, 0, -1), (0, 0, 1), (0, 1, 0)]
+            )
+        );
+    }
+
+    #[test]
+    fn test_identical_placements_collapse() {
+        let ants = HashMap::from([(PieceKind::Ant, 3)]);
+        let state = State::default()
+            .with_unplaced(Pieces::from_counts(&ants, Player::P1).with_counts(&ants, Player::P2));
+
+        let opening = state.successors();
+        assert_eq!(opening.len(), 1);
+        let reply = opening[0].1.successors();
+        assert_eq!(reply.len(), 6);
+        let third = reply[0].1.successors();
+        assert_eq!(third.iter().filter(|(mv, _)| mv.is_placement()).count(), 3);
+        for successors in [opening, reply, third] {
+            assert!(successors.iter().map(|(mv, _)| mv).all_unique());
+            assert!(successors.iter().map(|(_, state)| state).all_unique());
+        }
+        assert_eq!(state.perft(2), 6);
+    }
+
+    #[test]
+    fn test_placement_order() {
+        let kinds = |state: &State| {
+            state
+                .successors()
+                .into_iter()
+                .filter_map(|(mv, _)| mv.piece_kind())
+                .dedup()
+                .collect_vec()
+        };
+
+        assert_eq!(
+            kinds(&State::default()),
+            [
+                PieceKind::Queen,
+                PieceKind::Spider,
+                PieceKind::Beetle,
+                PieceKind::Grasshopper,
+                PieceKind::Ant,
+            ]
+        );
+        // however the hand was built
+        let counts = [
+            (PieceKind::Mosquito, 1),
+            (PieceKind::Ant, 2),
+            (PieceKind::Spider, 1),
+        ];
+        let forwards = HashMap::from(counts);
+        let backwards = counts.into_iter().rev().collect();
+        for counts in [forwards, backwards] {
+            let state = State::default().with_unplaced(Pieces::from_counts(&counts, Player::P1));
+            assert_eq!(
+                kinds(&state),
+                [PieceKind::Spider, PieceKind::Ant, PieceKind::Mosquito]
+            );
+            assert_eq!(
+                state.unplaced.kinds(Player::P1).collect_vec(),
+                kinds(&state)
+            );
+            assert_eq!(state.unplaced.kinds(Player::P2).count(), 0);
+        }
+    }
+
+    #[test]
+    fn test_perft_divide() {
+        let state = State::default();
+
+        let divide = state.perft_divide(3);
+
+        assert_eq!(divide.len(), 5);
+        assert!(divide.is_sorted());
+        assert_eq!(divide.iter().map(|(_, n)| n).sum::<u64>(), state.perft(3));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_perft_parallel() {
+        let state = State::default();
+
+        assert_eq!(state.perft_parallel(0), 1);
+        assert_eq!(state.perft_parallel(3), state.perft(3));
+    }
+
+    #[test]
+    fn test_random_playout() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let ring = Point::new(0, 0, 0).neighbors();
+        let won = State {
+            turn: 6,
+            queens: [Some(ring[0]), Some(Point::new(0, 0, 0))],
+            board: Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P2)),
+                (ring[0], Piece::Queen(Player::P1)),
+                (ring[1], Piece::Ant(Player::P1)),
+                (ring[2], Piece::Ant(Player::P1)),
+                (ring[3], Piece::Spider(Player::P1)),
+                (ring[4], Piece::Spider(Player::P1)),
+                (ring[5], Piece::Beetle(Player::P1)),
+            ]),
+            ..Default::default()
+        };
+
+        assert_eq!(won.random_playout(&mut rng, 0), GameResult::P1Wins);
+        assert_eq!(won.random_playout(&mut rng, 10), GameResult::P1Wins);
+        assert_eq!(
+            State::default().random_playout(&mut rng, 0),
+            GameResult::Draw
+        );
+        for _ in 0..3 {
+            assert_ne!(
+                State::default().random_playout(&mut rng, 30),
+                GameResult::InProgress
+            );
+        }
+        assert!(
+            State::default()
+                .successors()
+                .iter()
+                .any(|(mv, _)| *mv == State::default().random_move(&mut rng))
+        );
+    }
+
+    #[test]
+    fn test_is_connected() {
+        let board = Board::from([
+            (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+            (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+            (Point::new(0, 1, 0), Piece::Ant(Player::P1)),
+        ]);
+
+        assert!(board.is_connected());
+    }
+
+    #[test]
+    fn test_would_break_hive() {
+        let mut board = Board::from([
+            (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+            (Point::new(1, 0, 0), Piece::Ant(Player::P1)),
+            (Point::new(2, 0, 0), Piece::Queen(Player::P2)),
+        ]);
+
+        assert!(board.would_break_hive(Point::new(1, 0, 0)));
+        assert!(!board.would_break_hive(Point::new(0, 0, 0)));
+        assert!(!board.would_break_hive(Point::new(5, 0, 0)));
+
+        board.place(Point::new(1, 0, 0), Piece::Beetle(Player::P2));
+        assert!(!board.would_break_hive(Point::new(1, 0, 0)));
+
+        board.place(Point::new(3, 0, 0), Piece::Beetle(Player::P2));
+        assert!(board.would_break_hive(Point::new(2, 0, 0)));
+        assert!(!board.would_break_hive(Point::new(3, 0, 0)));
+    }
+
+    #[test]
+    fn test_pins() {
+        let state = State::default().with_board(Board::from([
+            (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+            (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+            (Point::new(0, 1, 0), Piece::Spider(Player::P1)),
+            (Point::new(0, 1, 0), Piece::Beetle(Player::P2)),
+            (Point::new(-1, 0, 0), Piece::Ant(Player::P1)),
+            (Point::new(-2, 0, 0), Piece::Grasshopper(Player::P2)),
+        ]));
+
+        assert_eq!(
+            state.board.articulation_points(),
+            HashSet::from([Point::new(0, 0, 0), Point::new(-1, 0, 0)])
+        );
+        assert_eq!(
+            state.pins(Player::P1),
+            HashSet::from([
+                Point::new(0, 0, 0),
+                Point::new(-1, 0, 0),
+                Point::new(0, 1, 0)
+            ])
+        );
+        assert!(state.pins(Player::P2).is_empty());
+        assert!(
+            state.board.keys().all(|&p| state.board.would_break_hive(p)
+                == state.board.articulation_points().contains(&p))
+        );
+    }
+
+    #[test]
+    fn test_adjacency_graph() {
+        let board = Board::from([
+            (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+            (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+            (Point::new(0, 1, 0), Piece::Ant(Player::P1)),
+            (Point::new(-1, 0, 0), Piece::Ant(Player::P2)),
+        ]);
+
+        let graph = board.adjacency_graph();
+        assert_eq!(graph.len(), 4);
+        assert_eq!(graph[&Point::new(0, 0, 0)].len(), 3);
+        assert_eq!(graph[&Point::new(1, 0, 0)].len(), 2);
+        assert_eq!(graph[&Point::new(0, 1, 0)].len(), 2);
+        assert_eq!(graph[&Point::new(-1, 0, 0)], vec![Point::new(0, 0, 0)]);
+    }
+
+    #[test]
+    fn test_equals_up_to_translation() {
+        let board = Board::from([
+            (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+            (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+            (Point::new(1, 0, 0), Piece::Beetle(Player::P1)),
+            (Point::new(0, 1, 0), Piece::Ant(Player::P1)),
+        ]);
+        let offset = Point::new(-4, 2, 7);
+        let translated = board.translated(offset);
+        let rotated = board.rotate60(1);
+
+        assert_ne!(translated, board);
+        assert!(board.equals_up_to_translation(&translated));
+        assert!(translated.equals_up_to_translation(&board));
+        assert!(!board.equals_up_to_translation(&rotated));
+        assert!(!board.equals_up_to_translation(&board.retain_player(Player::P1)));
+        assert!(Board::default().equals_up_to_translation(&Board::default()));
+    }
+
+    #[test]
+    fn test_board_symmetries() {
+        let board = Board::from([
+            (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+            (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+            (Point::new(1, 0, 0), Piece::Beetle(Player::P1)),
+            (Point::new(0, 1, 0), Piece::Ant(Player::P1)),
+            (Point::new(2, 0, 1), Piece::Spider(Player::P2)),
+        ]);
+
+        let turned = (0..6).fold(board.clone(), |b, _| b.rotate60(1));
+        assert_eq!(turned, board);
+        assert!(turned.equals_up_to_translation(&board));
+        assert_eq!(board.rotate60(2).rotate60(-2), board);
+        assert!(!board.rotate60(1).equals_up_to_translation(&board));
+        for axis in [Axis::X, Axis::Y, Axis::Z] {
+            let reflected = board.reflect(axis);
+            assert_ne!(reflected, board);
+            assert_eq!(reflected.reflect(axis), board);
+            assert!(reflected.is_connected());
+            assert_eq!(
+                *reflected[&Point::new(1, 0, 0).reflect(axis)],
+                *board[&Point::new(1, 0, 0)]
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_connected_disconnected() {
+        let board = Board::from([
+            (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+            (Point::new(1, 0, 0), Piece::Ant(Player::P1)),
+            (Point::new(4, 0, 0), Piece::Queen(Player::P2)),
+            (Point::new(5, 0, 0), Piece::Ant(Player::P2)),
+        ]);
+
+        assert!(!board.is_connected());
+    }
+
+    #[test]
+    fn test_is_connected_trivial() {
+        let mut board = Board::default();
+        assert!(board.is_connected());
+
+        board.place(Point::new(3, -2, 1), Piece::Spider(Player::P2));
+        assert!(board.is_connected());
+    }
+
+    #[test]
+    fn test_placeable_points_contact() {
+        let state = State {
+            turn: 1,
+            queens: [Some(Point::new(0, 0, 0)), Some(Point::new(1, 0, 0))],
+            board: Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+            ]),
+            ..Default::default()
+        };
+
+        let points = state.placeable_points();
+
+        assert_eq!(points.len(), 3);
+        assert!(points.contains(&Point::new(-1, 0, 0)));
+        assert!(!points.contains(&Point::new(0, 1, 0)));
+        assert!(!points.contains(&Point::new(0, 0, 0)));
+    }
+
+    #[test]
+    fn test_placeable_points_friendly_only() {
+        let state = State {
+            turn: 2,
+            queens: [Some(Point::new(0, 0, 0)), None],
+            board: Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(1, 0, 0), Piece::Ant(Player::P1)),
+            ]),
+            ..Default::default()
+        };
+
+        let points = state.placeable_points();
+
+        assert_eq!(points.len(), 8);
+        assert!(points.contains(&Point::new(0, 1, 0)));
+    }
+
+    #[test]
+    fn test_move_predicates() {
+        let place = Move::Place {
+            kind: PieceKind::Ant,
+            to: Point::new(0, 0, 1),
+        };
+        let relocate = Move::Relocate {
+            kind: PieceKind::Beetle,
+            from: Point::new(0, 0, 0),
+            to: Point::new(1, 0, 0),
+        };
+        let pass = Move::Pass;
+
+        assert!(place.is_placement() && !place.is_movement() && !place.is_pass());
+        assert!(!relocate.is_placement() && relocate.is_movement() && !relocate.is_pass());
+        assert!(!pass.is_placement() && !pass.is_movement() && pass.is_pass());
+
+        assert_eq!(place.piece_kind(), Some(PieceKind::Ant));
+        assert_eq!(relocate.piece_kind(), Some(PieceKind::Beetle));
+        assert_eq!(pass.piece_kind(), None);
+
+        assert_eq!(place.destination(), Some(Point::new(0, 0, 1)));
+        assert_eq!(relocate.destination(), Some(Point::new(1, 0, 0)));
+        assert_eq!(pass.destination(), None);
+    }
+
+    #[test]
+    fn test_notation_short() {
+        let state = State::default().with_board(Board::from([
+            (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+            (Point::new(1, 0, 0), Piece::Spider(Player::P2)),
+            (Point::new(-1, 0, 0), Piece::Beetle(Player::P1)),
+        ]));
+
+        let place = Move::Place {
+            kind: PieceKind::Ant,
+            to: Point::new(0, 0, 2),
+        };
+        let slide = Move::Relocate {
+            kind: PieceKind::Queen,
+            from: Point::new(0, 0, 0),
+            to: Point::new(0, 1, 0),
+        };
+        let climb = Move::Relocate {
+            kind: PieceKind::Beetle,
+            from: Point::new(-1, 0, 0),
+            to: Point::new(1, 0, 0),
+        };
+
+        assert_eq!(place.notation_short(&state), "Place Ant at (0,0,2)");
+        assert_eq!(slide.notation_short(&state), "Move Queen to (0,1,0)");
+        assert_eq!(climb.notation_short(&state), "Move Beetle onto Spider");
+        assert_eq!(Move::Pass.notation_short(&state), "Pass");
+    }
+
+    #[test]
+    fn test_grasshopper_jumps() {
+        let board = Board::from([
+            (Point::new(-1, 0, 0), Piece::Grasshopper(Player::P1)),
+            (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+            (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+            (Point::new(2, 0, 0), Piece::Ant(Player::P2)),
+        ]);
+
+        let jumps = grasshopper_jumps(Point::new(-1, 0, 0), &board);
+
+        assert_eq!(
+            jumps,
+            vec![(
+                Point::new(3, 0, 0),
+                vec![
+                    Point::new(0, 0, 0),
+                    Point::new(1, 0, 0),
+                    Point::new(2, 0, 0)
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_grasshopper_jumps_every_direction() {
+        // stepping east from (0, 0, 1) naively gives (1, 0, 1), which is (0, 1, 0) written
+        // the long way
+        let center = Point::new(0, 0, 1);
+        let mut board = Board::from([(center, Piece::Grasshopper(Player::P1))]);
+        for n in center.neighbors() {
+            let step = n - center;
+            board.place(n, Piece::Ant(Player::P2));
+            board.place(n + step, Piece::Spider(Player::P2));
+        }
+
+        let jumps = grasshopper_jumps(center, &board);
+
+        assert_eq!(jumps.len(), 6);
+        for ((to, jumped), n) in jumps.into_iter().zip(center.neighbors()) {
+            let step = n - center;
+            assert_eq!(jumped, vec![n, n + step]);
+            assert_eq!(to, n + step + step);
+            assert_eq!(to, to.canonicalize());
+            assert!(!board.contains_key(&to));
+        }
+        assert!(
+            grasshopper_jumps(center, &board)
+                .iter()
+                .any(|(to, _)| *to == Point::new(2, 1, 0))
+        );
+    }
+
+    #[test]
+    fn test_flip_perspective() {
+        let state = State {
+            turn: 2,
+            active: Player::P2,
+            queens: [Some(Point::new(0, 0, 0)), None],
+            unplaced: Pieces::from_counts(&HashMap::from([(PieceKind::Ant, 1)]), Player::P1),
+            board: Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(0, 0, 1), Piece::Spider(Player::P1)),
+                (Point::new(0, 0, 1), Piece::Beetle(Player::P2)),
+            ]),
+            rules: Rules::default(),
+            placed: PlacementOrder::default(),
+        };
+
+        let flipped = state.flip_perspective();
+
+        assert_eq!(flipped.active, Player::P1);
+        assert_eq!(flipped.queen(Player::P2), Some(Point::new(0, 0, 0)));
+        assert_eq!(flipped.queen(Player::P1), None);
+        assert_eq!(flipped.unplaced.p2, vec![Piece::Ant(Player::P2)]);
+        assert_eq!(
+            *flipped.board[&Point::new(0, 0, 1)],
+            [Piece::Spider(Player::P2), Piece::Beetle(Player::P1)]
+        );
+        assert_eq!(flipped.flip_perspective(), state);
+    }
+
+    #[test]
+    fn test_loses_immediately() {
+        let state = State {
+            turn: 3,
+            queens: [Some(Point::new(0, 0, 0)), Some(Point::new(-1, 0, 0))],
+            board: Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(-1, 0, 0), Piece::Queen(Player::P2)),
+                (Point::new(0, -1, 0), Piece::Grasshopper(Player::P2)),
+                (Point::new(0, 0, -1), Piece::Grasshopper(Player::P2)),
+                (Point::new(1, 0, 0), Piece::Spider(Player::P2)),
+                (Point::new(0, 1, 0), Piece::Spider(Player::P2)),
+                (Point::new(-1, 0, 1), Piece::Ant(Player::P1)),
+            ]),
+            ..Default::default()
+        };
+        let suicide = Move::Relocate {
+            kind: PieceKind::Ant,
+            from: Point::new(-1, 0, 1),
+            to: Point::new(0, 0, 1),
+        };
+
+        assert!(state.loses_immediately(&suicide));
+        assert!(
+            state
+                .legal_moves_for(Point::new(-1, 0, 1))
+                .iter()
+                .filter(|mv| state.loses_immediately(mv))
+                .eq([&suicide])
+        );
+    }
+
+    #[test]
+    fn test_board_into_iter() {
+        let board = Board::from([
+            (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+            (Point::new(0, 0, 1), Piece::Queen(Player::P2)),
+            (Point::new(-1, 0, 0), Piece::Ant(Player::P1)),
+        ]);
+
+        let mut p1_pieces = 0;
+        for (_, stack) in &board {
+            if stack.top().player() == Player::P1 {
+                p1_pieces += 1;
+            }
+        }
+
+        assert_eq!(p1_pieces, 2);
+    }
+
+    #[test]
+    fn test_movement_rule() {
+        assert_eq!(PieceKind::Spider.movement_rule().distance, Some(3));
+        assert_eq!(PieceKind::Ant.movement_rule().distance, None);
+        assert!(PieceKind::Beetle.movement_rule().climbs);
+        assert!(!PieceKind::Queen.movement_rule().climbs);
+        assert!(PieceKind::Grasshopper.movement_rule().jumps);
+        assert!(!PieceKind::Grasshopper.movement_rule().slides);
+    }
+
+    #[test]
+    fn test_legal_moves_count_by_kind() {
+        let state = State {
+            turn: 3,
+            queens: [Some(Point::new(0, 0, 0)), Some(Point::new(2, 0, 0))],
+            unplaced: Pieces::from_counts(&HashMap::new(), Player::P1),
+            board: Board::from([
+                (Point::new(-1, 0, 0), Piece::Ant(Player::P1)),
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(1, 0, 0), Piece::Spider(Player::P1)),
+                (Point::new(2, 0, 0), Piece::Queen(Player::P2)),
+            ]),
+            ..Default::default()
+        };
+
+        let counts = state.legal_moves_count_by_kind();
+
+        assert_eq!(
+            counts,
+            HashMap::from([(
+                PieceKind::Ant,
+                state.legal_moves_for(Point::new(-1, 0, 0)).len()
+            )])
+        );
+        assert!(counts[&PieceKind::Ant] > 0);
+        assert!(!counts.contains_key(&PieceKind::Spider));
+    }
+
+    #[test]
+    fn test_ring() {
+        let center = Point::new(2, 0, -1);
+
+        assert_eq!(center.ring(0), vec![center]);
+        assert_eq!(
+            center.ring(1).into_iter().collect::<HashSet<_>>(),
+            center.neighbors().into_iter().collect::<HashSet<_>>()
+        );
+
+        let ring = center.ring(3);
+        assert_eq!(ring.len(), 18);
+        assert_eq!(ring.iter().unique().count(), 18);
+        assert!(ring.iter().all(|p| p.distance(&center) == 3));
+    }
+
+    #[test]
+    fn test_point_arithmetic() {
+        let origin = Point::new(0, 0, 0);
+        let offsets = origin.spiral(2);
+
+        for p in origin.spiral(3) {
+            for &offset in &offsets {
+                assert_eq!(p + offset - offset, p);
+                assert_eq!((p + offset).distance(&p), offset.distance(&origin));
+            }
+        }
+        assert_eq!(
+            Point::new(1, 0, 0) + Point::new(0, 0, 1),
+            Point::new(0, 1, 0)
+        );
+        assert_eq!(
+            Point::new(2, 0, 0) - Point::new(1, 0, 0),
+            Point::new(1, 0, 0)
+        );
+        assert_eq!(
+            origin.neighbors().iter().map(|&n| n - origin).collect_vec(),
+            origin.neighbors()
+        );
+    }
+
+    #[test]
+    fn test_rotate60() {
+        let origin = Point::new(0, 0, 0);
+        let neighbors = origin.neighbors();
+
+        for p in origin.spiral(3) {
+            assert_eq!(p.rotate60(6), p);
+            assert_eq!(p.rotate60(-1), p.rotate60(5));
+            assert_eq!(p.rotate60(2).rotate60(3), p.rotate60(5));
+            assert_eq!(p.rotate60(1).distance(&origin), p.distance(&origin));
+        }
+        for (i, n) in neighbors.iter().enumerate() {
+            assert_eq!(n.rotate60(1), neighbors[(i + 1) % 6]);
+        }
+        let p = Point::new(3, -1, 2);
+        assert_eq!(
+            p.rotate60(1).neighbors().into_iter().sorted().collect_vec(),
+            p.neighbors()
+                .iter()
+                .map(|n| n.rotate60(1))
+                .sorted()
+                .collect_vec()
+        );
+    }
+
+    #[test]
+    fn test_reflect() {
+        let origin = Point::new(0, 0, 0);
+
+        for (axis, fixed) in [
+            (Axis::X, Point::new(1, 0, 0)),
+            (Axis::Y, Point::new(0, 1, 0)),
+            (Axis::Z, Point::new(0, 0, 1)),
+        ] {
+            assert_eq!(fixed.reflect(axis), fixed);
+            for p in origin.spiral(3) {
+                assert_eq!(p.reflect(axis).reflect(axis), p);
+                assert_eq!(p.reflect(axis).distance(&origin), p.distance(&origin));
+            }
+        }
+        assert_eq!(Point::new(0, 1, 0).reflect(Axis::X), Point::new(0, 0, -1));
+    }
+
+    #[test]
+    fn test_neighbors_with_direction() {
+        let p = Point::new(2, -1, 3);
+        let neighbors = p.neighbors_with_direction();
+
+        assert_eq!(neighbors.map(|(n, _)| n).to_vec(), p.neighbors());
+        assert_eq!(neighbors.map(|(_, d)| d), Direction::ALL);
+        for (n, d) in neighbors {
+            let step = match d {
+                Direction::W => Point::new(-1, 0, 0),
+                Direction::SW => Point::new(0, -1, 0),
+                Direction::SE => Point::new(0, 0, -1),
+                Direction::E => Point::new(1, 0, 0),
+                Direction::NE => Point::new(0, 1, 0),
+                Direction::NW => Point::new(0, 0, 1),
+            };
+            assert_eq!(n, p + step);
+            assert_eq!(n + (Point::new(0, 0, 0) - step), p);
+            assert_eq!(
+                n.neighbors_with_direction()[d.opposite() as usize],
+                (p, d.opposite())
+            );
+        }
+        assert_eq!(Direction::NE.opposite(), Direction::SW);
+        assert_eq!(Direction::E.opposite().opposite(), Direction::E);
+    }
+
+    #[test]
+    fn test_player_index() {
+        for player in [Player::P1, Player::P2] {
+            assert_eq!(Player::from_index(player.index()), Some(player));
+        }
+        assert_eq!(Player::P1.index(), 0);
+        assert_eq!((!Player::P1).index(), 1);
+        assert_eq!(Player::from_index(2), None);
+    }
+
+    #[test]
+    fn test_spiral() {
+        let spiral = Point::new(0, 0, 0).spiral(2);
+
+        assert_eq!(spiral.len(), 19);
+        assert_eq!(spiral.iter().unique().count(), 19);
+        assert_eq!(spiral[0], Point::new(0, 0, 0));
+    }
+
+    #[test]
+    fn test_double_surround_draw() {
+        // the queens sit side by side, and everything around them but one of the two hexes
+        // they share is filled, so a grasshopper landing there closes both rings at once
+        let origin = Point::new(0, 0, 0);
+        let [w, _, se, e, ne, _] = origin.neighbors()[..] else {
+            unreachable!()
+        };
+        let step = ne - origin;
+        let mut board = Board::from([
+            (origin, Piece::Queen(Player::P1)),
+            (e, Piece::Queen(Player::P2)),
+            (ne + step + step, Piece::Grasshopper(Player::P1)),
+        ]);
+        for p in origin.neighbors().into_iter().chain(e.neighbors()) {
+            if p != ne && !board.contains_key(&p) {
+                board.place(p, Piece::Ant(Player::P2));
+            }
+        }
+        board.place(ne + step, Piece::Spider(Player::P2));
+        let state = State::default()
+            .with_turn(8)
+            .with_queens(Some(origin), Some(e))
+            .with_unplaced(
+                Pieces::from_counts(&HashMap::new(), Player::P1)
+                    .with_counts(&HashMap::new(), Player::P2),
+            )
+            .with_board(board);
+        assert!(state.board.contains_key(&se));
+        assert_eq!(state.result(), GameResult::InProgress);
+
+        let closed = state.move_piece(ne + step + step, ne).unwrap();
+
+        assert!(closed.all_queens_surrounded());
+        assert_eq!(closed.result(), GameResult::Draw);
+        assert!(!state.all_queens_surrounded());
+
+        // with only one ring closed, the other side wins
+        let mut one = closed.board.clone();
+        one.remove(&w);
+        let one = closed.clone().with_board(one);
+        assert!(!one.all_queens_surrounded());
+        assert_eq!(one.result(), GameResult::P1Wins);
+    }
+
+    #[test]
+    fn test_apply_annotated() {
+        let state = snapshot(
+            Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+                (Point::new(-1, 0, 0), Piece::Ant(Player::P1)),
+            ]),
+            Point::new(0, 0, 0),
+            Point::new(1, 0, 0),
+        );
+        let mv = Move::Relocate {
+            kind: PieceKind::Ant,
+            from: Point::new(-1, 0, 0),
+            to: Point::new(2, 0, 0),
+        };
+
+        let (next, delta) = state.apply_annotated(mv).unwrap();
+
+        assert_eq!(Ok(next), state.apply(mv));
+        assert_eq!(
+            delta,
+            LibertyDelta {
+                p1_before: Some(4),
+                p1_after: Some(5),
+                p2_before: Some(5),
+                p2_after: Some(4),
+            }
+        );
+        assert_eq!(delta.change(Player::P1), Some(1));
+        assert_eq!(delta.change(Player::P2), Some(-1));
+        assert_eq!(LibertyDelta::default().change(Player::P1), None);
+        assert_eq!(
+            state.apply_annotated(Move::Pass),
+            Err(HiveError::IllegalMove(Move::Pass))
+        );
+    }
+
+    #[test]
+    fn test_hand_is_empty() {
+        let state = snapshot(
+            Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+                (Point::new(-1, 0, 0), Piece::Ant(Player::P1)),
+            ]),
+            Point::new(0, 0, 0),
+            Point::new(1, 0, 0),
+        )
+        .with_unplaced(
+            Pieces::from_counts(&HashMap::new(), Player::P1)
+                .with_counts(&HashMap::from([(PieceKind::Ant, 1)]), Player::P2),
+        );
+
+        assert!(state.hand_is_empty(Player::P1));
+        assert!(!state.hand_is_empty(Player::P2));
+        assert!(state.legal_moves().iter().all(Move::is_movement));
+        assert!(!State::default().hand_is_empty(Player::P1));
+
+        let deployed = state
+            .with_active(Player::P2)
+            .place(PieceKind::Ant, Point::new(2, 0, 0))
+            .unwrap();
+        assert!(deployed.hand_is_empty(Player::P2));
+    }
+
+    #[test]
+    fn test_dense_position_generation_budget() {
+        // every piece on the board in a tight blob, with the crawlers on the outside where they
+        // have the longest walks and the most branching paths
+        let rank = |piece: &Piece| match piece.kind() {
+            PieceKind::Queen => 0,
+            PieceKind::Beetle => 1,
+            PieceKind::Grasshopper => 2,
+            PieceKind::Mosquito => 3,
+            PieceKind::Spider => 4,
+            PieceKind::Ant => 5,
+        };
+        let full = Pieces::new();
+        let pieces = full
+            .p1
+            .iter()
+            .interleave(&full.p2)
+            .sorted_by_key(|piece| rank(piece))
+            .copied()
+            .collect_vec();
+        let board = Point::new(0, 0, 0)
+            .spiral(3)
+            .into_iter()
+            .zip(pieces.iter().copied())
+            .collect::<Board>();
+        let queen = |player| {
+            board
+                .iter()
+                .find(|(_, stack)| *stack.top() == Piece::Queen(player))
+                .map(|(&point, _)| point)
+        };
+        let state = State::default()
+            .with_turn(11)
+            .with_queens(queen(Player::P1), queen(Player::P2))
+            .with_unplaced(
+                Pieces::from_counts(&HashMap::new(), Player::P1)
+                    .with_counts(&HashMap::new(), Player::P2),
+            )
+            .with_board(board);
+        assert_eq!(state.board.len(), pieces.len());
+        assert_eq!(state.validate_full(&full), Ok(()));
+
+        let start = std::time::Instant::now();
+        for player in [Player::P1, Player::P2] {
+            let state = state.clone().with_active(player);
+            let moves = state.legal_moves();
+            // no piece can have more destinations than there are empty hexes next to the hive
+            let perimeter = state
+                .board
+                .keys()
+                .flat_map(|&p| state.board.empty_neighbors(p))
+                .distinct()
+                .count();
+            assert!(!moves.is_empty());
+            assert!(moves.len() <= pieces.len() / 2 * perimeter);
+        }
+        // generation is linear in the board for ants and bounded by three steps for spiders, so
+        // this takes tens of milliseconds even unoptimized; anything exponential would take far
+        // longer
+        assert!(start.elapsed() < std::time::Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_max_turns() {
+        let state = State {
+            turn: 11,
+            queens: [Some(Point::new(0, 0, 0)), Some(Point::new(0, 0, 1))],
+            board: Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(0, 0, 1), Piece::Queen(Player::P2)),
+            ]),
+            rules: Rules {
+                max_turns: Some(10),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(state.result(), GameResult::Draw);
+        assert_eq!(state.clone().with_turn(10).result(), GameResult::InProgress);
+        assert_eq!(
+            state.clone().with_rules(Rules::default()).result(),
+            GameResult::InProgress
+        );
+        assert!(!state.get_moves().is_empty());
+    }
+
+    #[test]
+    fn test_material_balance() {
+        let state = State {
+            turn: 2,
+            queens: [Some(Point::new(0, 0, 0)), Some(Point::new(0, 0, 1))],
+            board: Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(0, 0, 1), Piece::Queen(Player::P2)),
+                (Point::new(0, 0, 1), Piece::Beetle(Player::P1)),
+                (Point::new(-1, 0, 0), Piece::Ant(Player::P1)),
+                (Point::new(0, 0, 2), Piece::Spider(Player::P2)),
+            ]),
+            ..Default::default()
+        };
+        let weights = PieceWeights {
+            queen: 0,
+            beetle: 3,
+            ant: 5,
+            grasshopper: 2,
+            spider: 1,
+            mosquito: 3,
+        };
+
+        assert_eq!(state.material_balance(&weights), 7);
+        assert_eq!(state.flip_perspective().material_balance(&weights), -7);
+    }
+
+    #[test]
+    fn test_climb_and_descend() {
+        let mut board = Board::from([
+            (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+            (Point::new(1, 0, 0), Piece::Beetle(Player::P1)),
+            (Point::new(0, 1, 0), Piece::Beetle(Player::P2)),
+        ]);
+
+        board
+            .climb(Point::new(1, 0, 0), Point::new(0, 0, 0))
+            .unwrap();
+        assert_eq!(
+            *board[&Point::new(0, 0, 0)],
+            [Piece::Queen(Player::P1), Piece::Beetle(Player::P1)]
+        );
+        assert!(!board.contains_key(&Point::new(1, 0, 0)));
+
+        board
+            .climb(Point::new(0, 1, 0), Point::new(0, 0, 0))
+            .unwrap();
+        assert_eq!(
+            *board[&Point::new(0, 0, 0)],
+            [
+                Piece::Queen(Player::P1),
+                Piece::Beetle(Player::P1),
+                Piece::Beetle(Player::P2)
+            ]
+        );
+
+        board
+            .descend(Point::new(0, 0, 0), Point::new(0, 1, 0))
+            .unwrap();
+        assert_eq!(*board[&Point::new(0, 1, 0)], [Piece::Beetle(Player::P2)]);
+        assert_eq!(
+            *board[&Point::new(0, 0, 0)],
+            [Piece::Queen(Player::P1), Piece::Beetle(Player::P1)]
+        );
+    }
+
+    #[test]
+    fn test_multi_beetle_stack() {
+        let state = State {
+            turn: 3,
+            queens: [Some(Point::new(0, 0, 0)), Some(Point::new(1, 0, 0))],
+            board: Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+                (Point::new(1, 0, 0), Piece::Beetle(Player::P1)),
+                (Point::new(1, 0, 0), Piece::Beetle(Player::P2)),
+            ]),
+            ..Default::default()
+        };
+
+        assert!(state.legal_moves_for(Point::new(1, 0, 0)).is_empty());
+
+        let state = state.with_active(Player::P2);
+        assert_eq!(state.legal_moves_for(Point::new(1, 0, 0)).len(), 6);
+
+        let state = state
+            .apply(Move::Relocate {
+                kind: PieceKind::Beetle,
+                from: Point::new(1, 0, 0),
+                to: Point::new(0, 0, 0),
+            })
+            .unwrap();
+        assert_eq!(
+            *state.board[&Point::new(1, 0, 0)],
+            [Piece::Queen(Player::P2), Piece::Beetle(Player::P1)]
+        );
+        assert_eq!(
+            *state.board[&Point::new(0, 0, 0)],
+            [Piece::Queen(Player::P1), Piece::Beetle(Player::P2)]
+        );
+        assert!(state.legal_moves_for(Point::new(0, 0, 0)).is_empty());
+        assert_eq!(state.legal_moves_for(Point::new(1, 0, 0)).len(), 6);
+    }
+
+    #[test]
+    fn test_legal_moves_covered_beetle() {
+        let stack = Point::new(1, 0, 0);
+        let state = State {
+            turn: 3,
+            queens: [Some(Point::new(0, 0, 0)), Some(stack)],
+            unplaced: Pieces::from_counts(&HashMap::new(), Player::P1),
+            board: Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (stack, Piece::Queen(Player::P2)),
+                (stack, Piece::Beetle(Player::P1)),
+                (stack, Piece::Beetle(Player::P1)),
+            ]),
+            ..Default::default()
+        };
+
+        let from_stack = state
+            .successors()
+            .into_iter()
+            .filter(|(mv, _)| matches!(mv, Move::Relocate { from, .. } if *from == stack))
+            .collect_vec();
+
+        assert_eq!(from_stack.len(), 6);
+        // the rest are the queen sliding around the stack
+        assert_eq!(state.legal_moves().len(), 6 + 2);
+        for (_, next) in from_stack {
+            assert_eq!(
+                *next.board[&stack],
+                [Piece::Queen(Player::P2), Piece::Beetle(Player::P1)]
+            );
+        }
+    }
+
+    #[test]
+    fn test_game_result_helpers() {
+        assert_eq!(GameResult::InProgress.winner(), None);
+        assert_eq!(GameResult::P1Wins.winner(), Some(Player::P1));
+        assert_eq!(GameResult::P2Wins.winner(), Some(Player::P2));
+        assert_eq!(GameResult::Draw.winner(), None);
+
+        assert!(!GameResult::InProgress.is_draw());
+        assert!(!GameResult::P1Wins.is_draw());
+        assert!(!GameResult::P2Wins.is_draw());
+        assert!(GameResult::Draw.is_draw());
+
+        assert!(!GameResult::InProgress.is_over());
+        assert!(GameResult::P1Wins.is_over());
+        assert!(GameResult::P2Wins.is_over());
+        assert!(GameResult::Draw.is_over());
+    }
+
+    #[test]
+    fn test_coverage_map() {
+        // P1's ant sits behind her queen, P2 has only a queen and a spider
+        let state = State::default()
+            .with_turn(2)
+            .with_queens(Some(Point::new(0, 0, 0)), Some(Point::new(1, 0, 0)))
+            .with_board(Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(-1, 0, 0), Piece::Ant(Player::P1)),
+                (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+                (Point::new(2, 0, 0), Piece::Spider(Player::P2)),
+            ]));
+
+        let map = state.coverage_map();
+
+        assert_eq!(map.len(), 12);
+        assert!(map.keys().all(|p| !state.board.contains_key(p)));
+        for (&point, coverage) in &map {
+            let state = state.clone().with_active(Player::P1);
+            assert_eq!(coverage.p1_place, state.placeable_points().contains(&point));
+            assert_eq!(
+                coverage.p1_move,
+                state
+                    .board
+                    .keys()
+                    .any(|&from| state.legal_destinations(from).contains(&point))
+            );
+        }
+        // next to both queens, so nobody can place there, and P2's queen can't leave the middle
+        // of the hive
+        assert_eq!(
+            map[&Point::new(0, 1, 0)],
+            Coverage {
+                p1_place: false,
+                p1_move: true,
+                p2_place: false,
+                p2_move: false,
+            }
+        );
+        assert!(map[&Point::new(-2, 0, 0)].p1_place);
+        assert!(!map[&Point::new(-2, 0, 0)].reachable_by(Player::P2));
+        assert!(map[&Point::new(3, 0, 0)].p2_place);
+        assert!(map.values().all(|c| c.reachable_by(Player::P1)));
+    }
+
+    #[test]
+    fn test_board_extend() {
+        let mut board = Board::from([(Point::new(0, 0, 0), Piece::Queen(Player::P1))]);
+
+        board.extend([
+            (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+            (Point::new(1, -1, 1), Piece::Beetle(Player::P1)),
+            (Point::new(0, 1, 0), Piece::Ant(Player::P2)),
+        ]);
+        board.extend(None);
+
+        assert_eq!(board.len(), 3);
+        assert_eq!(
+            board.top(&Point::new(0, 0, 0)),
+            Some(&Piece::Beetle(Player::P1))
+        );
+        assert_eq!(board[&Point::new(0, 0, 0)].height(), 2);
+        assert_eq!(
+            board.top(&Point::new(1, 0, 0)),
+            Some(&Piece::Queen(Player::P2))
+        );
+        assert_eq!(
+            board.top(&Point::new(1, 0, 1)),
+            Some(&Piece::Ant(Player::P2))
+        );
+        assert_eq!(
+            board,
+            Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(0, 0, 0), Piece::Beetle(Player::P1)),
+                (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+                (Point::new(0, 1, 0), Piece::Ant(Player::P2)),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_state_ordering() {
+        let state = State::default()
+            .with_turn(4)
+            .with_queens(Some(Point::new(0, 0, 0)), Some(Point::new(0, 0, 1)))
+            .with_board(Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(0, 0, 1), Piece::Queen(Player::P2)),
+                (Point::new(-1, 0, 0), Piece::Ant(Player::P1)),
+                (Point::new(0, -1, 0), Piece::Ant(Player::P1)),
+                (Point::new(0, 0, 2), Piece::Ant(Player::P2)),
+                (Point::new(0, 0, -1), Piece::Grasshopper(Player::P1)),
+            ]));
+        let states = state.successors().into_iter().map(|(_, s)| s).collect_vec();
+        let sorted = states.iter().sorted().collect_vec();
+
+        assert!(states.len() > 10);
+        for (a, b) in sorted.iter().tuple_windows() {
+            assert_eq!(a.cmp(b), core::cmp::Ordering::Less);
+            assert_eq!(b.cmp(a), core::cmp::Ordering::Greater);
+        }
+        for a in &states {
+            let rebuilt = a.clone().with_board(
+                a.board
+                    .iter()
+                    .flat_map(|(&p, s)| s.iter().map(move |&piece| (p, piece)))
+                    .collect(),
+            );
+            assert_eq!(a.cmp(&rebuilt), core::cmp::Ordering::Equal);
+            assert_eq!(a, &rebuilt);
+        }
+        assert_eq!(
+            states
+                .iter()
+                .collect::<alloc::collections::BTreeSet<_>>()
+                .len(),
+            states.len()
+        );
+    }
+
+    #[test]
+    fn test_climbing_rule() {
+        let state = snapshot(
+            Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+                (Point::new(-1, 0, 0), Piece::Beetle(Player::P1)),
+            ]),
+            Point::new(0, 0, 0),
+            Point::new(1, 0, 0),
+        )
+        .with_unplaced(Pieces::from_counts(&HashMap::new(), Player::P1));
+        let grounded = state.clone().with_rules(Rules {
+            climbing: false,
+            ..Rules::default()
+        });
+        let beetle = |state: &State| state.legal_destinations(Point::new(-1, 0, 0));
+
+        // two slides around the queen, plus climbing onto her
+        assert_eq!(beetle(&state).len(), 3);
+        assert!(beetle(&state).contains(&Point::new(0, 0, 0)));
+        assert_eq!(beetle(&grounded).len(), 2);
+        assert!(beetle(&grounded).is_subset(&beetle(&state)));
+        assert!(
+            beetle(&grounded)
+                .iter()
+                .all(|to| !state.board.contains_key(to))
+        );
+        assert_eq!(state.legal_moves().len() - grounded.legal_moves().len(), 1);
+    }
+
+    #[test]
+    fn test_beetle_gate() {
+        let origin = Point::new(0, 0, 0);
+        let [w, sw, se, e, ne, nw] = origin.neighbors()[..] else {
+            unreachable!()
+        };
+        // two tall stacks either side of the step from the origin onto the queen to the east
+        let gated = |mover: &[Piece]| {
+            let mut board = Board::from([
+                (e, Piece::Queen(Player::P2)),
+                (se, Piece::Ant(Player::P1)),
+                (se, Piece::Beetle(Player::P2)),
+                (ne, Piece::Ant(Player::P2)),
+                (ne, Piece::Beetle(Player::P1)),
+            ]);
+            board.extend(mover.iter().map(|&piece| (origin, piece)));
+            board
+        };
+
+        for kind in [PieceKind::Beetle, PieceKind::Mosquito] {
+            let piece = Piece::new(kind, Player::P1);
+            let board = gated(&[piece]);
+            assert!(!beetle_gate(origin, e, &board));
+            assert_eq!(
+                piece_moves(kind, origin, &board)
+                    .into_iter()
+                    .collect::<HashSet<_>>(),
+                HashSet::from([sw, se, ne, nw])
+            );
+            assert!(!piece_moves(kind, origin, &board).contains(&w));
+
+            // one level up it's still too low to get over the gap
+            let board = gated(&[Piece::Grasshopper(Player::P2), piece]);
+            assert!(!beetle_gate(origin, e, &board));
+            assert!(!piece_moves(kind, origin, &board).contains(&e));
+            assert!(piece_moves(kind, origin, &board).contains(&w));
+
+            // with one side lowered the gap opens up
+            let mut board = gated(&[piece]);
+            board.descend(se, Point::new(0, -3, 0)).unwrap();
+            assert!(beetle_gate(origin, e, &board));
+            assert!(piece_moves(kind, origin, &board).contains(&e));
+        }
+        assert!(!beetle_gate(origin, Point::new(2, 0, 0), &gated(&[])));
+    }
+
+    #[test]
+    fn test_beetle_on_roof() {
+        let origin = Point::new(0, 0, 0);
+        let [w, sw, se, e, ne, nw] = origin.neighbors()[..] else {
+            unreachable!()
+        };
+        let mut state = State::default()
+            .with_turn(6)
+            .with_queens(Some(w), Some(e))
+            .with_unplaced(Pieces::from_counts(&HashMap::new(), Player::P1))
+            .with_board(Board::from([
+                (w, Piece::Queen(Player::P1)),
+                (w, Piece::Beetle(Player::P1)),
+                (origin, Piece::Ant(Player::P2)),
+                (e, Piece::Queen(Player::P2)),
+            ]));
+
+        // along the top of the row, keeping the pieces underneath in place
+        for (from, to) in [(w, origin), (origin, e)] {
+            let walk = Move::Relocate {
+                kind: PieceKind::Beetle,
+                from,
+                to,
+            };
+            assert_eq!(state.board[&from].height(), 2);
+            assert_eq!(state.legal_destinations(from).len(), 6);
+            state = state.apply(walk).unwrap().with_active(Player::P1);
+            assert_eq!(state.board[&from].height(), 1);
+            assert_eq!(state.board.top(&to), Some(&Piece::Beetle(Player::P1)));
+        }
+        // then back down to the ground
+        let state = state
+            .apply(Move::Relocate {
+                kind: PieceKind::Beetle,
+                from: e,
+                to: Point::new(2, 0, 0),
+            })
+            .unwrap();
+        assert_eq!(state.board[&e].height(), 1);
+        assert_eq!(state.board[&Point::new(2, 0, 0)].height(), 1);
+
+        // on the roof, but still too low to fit between two taller stacks
+        let mut board = Board::from([
+            (origin, Piece::Ant(Player::P2)),
+            (origin, Piece::Beetle(Player::P1)),
+            (e, Piece::Queen(Player::P2)),
+        ]);
+        for flank in [ne, se] {
+            board.extend([
+                (flank, Piece::Ant(Player::P2)),
+                (flank, Piece::Beetle(Player::P2)),
+                (flank, Piece::Mosquito(Player::P2)),
+            ]);
+        }
+        assert_eq!(
+            beetle_moves(origin, &board)
+                .into_iter()
+                .collect::<HashSet<_>>(),
+            HashSet::from([w, sw, se, ne, nw])
+        );
+    }
+
+    #[test]
+    fn test_cells() {
+        let board = Board::from([
+            (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+            (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+            (Point::new(1, 0, 0), Piece::Beetle(Player::P1)),
+            (Point::new(1, 0, 0), Piece::Beetle(Player::P2)),
+        ]);
+
+        let cells = board
+            .cells()
+            .sorted_by_key(|&(point, _)| point)
+            .collect_vec();
+
+        assert_eq!(cells.len(), 2);
+        assert_eq!(cells[0].0, Point::new(0, 0, 0));
+        assert_eq!(**cells[0].1, [Piece::Queen(Player::P1)]);
+        assert_eq!(cells[1].0, Point::new(1, 0, 0));
+        assert_eq!(
+            **cells[1].1,
+            [
+                Piece::Queen(Player::P2),
+                Piece::Beetle(Player::P1),
+                Piece::Beetle(Player::P2),
+            ]
+        );
+        assert_eq!(
+            board
+                .cells()
+                .map(|(_, stack)| stack.height())
+                .sum::<usize>(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_climb_errors() {
+        let mut board = Board::from([
+            (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+            (Point::new(1, 0, 0), Piece::Beetle(Player::P1)),
+        ]);
+
+        assert_eq!(
+            board.climb(Point::new(0, 0, 0), Point::new(1, 0, 0)),
+            Err(HiveError::CannotClimb(Point::new(0, 0, 0)))
+        );
+        assert_eq!(
+            board.climb(Point::new(1, 0, 0), Point::new(2, 0, 0)),
+            Err(HiveError::Empty(Point::new(2, 0, 0)))
+        );
+        assert_eq!(
+            board.descend(Point::new(1, 0, 0), Point::new(0, 0, 0)),
+            Err(HiveError::Occupied(Point::new(0, 0, 0)))
+        );
+        assert_eq!(
+            board.descend(Point::new(3, 0, 0), Point::new(2, 0, 0)),
+            Err(HiveError::Empty(Point::new(3, 0, 0)))
+        );
+    }
+
+    #[test]
+    fn test_key() {
+        let board = |offset: Point| {
+            Board::from([
+                (
+                    Point::new(offset.x, offset.y, offset.z),
+                    Piece::Queen(Player::P1),
+                ),
+                (
+                    Point::new(offset.x, offset.y, offset.z + 1),
+                    Piece::Queen(Player::P2),
+                ),
+                (
+                    Point::new(offset.x, offset.y, offset.z + 1),
+                    Piece::Beetle(Player::P2),
+                ),
+                (
+                    Point::new(offset.x - 1, offset.y, offset.z),
+                    Piece::Ant(Player::P1),
+                ),
+            ])
+        };
+        let state = |offset| State {
+            turn: 2,
+            board: board(offset),
+            ..Default::default()
+        };
+
+        let key = state(Point::new(0, 0, 0)).key();
+
+        assert_eq!(key, state(Point::new(3, -2, 1)).key());
+        assert_eq!(key, state(Point::new(-5, 0, 4)).key());
+        assert_ne!(key, state(Point::new(0, 0, 0)).with_turn(3).key());
+        assert_ne!(
+            key,
+            state(Point::new(0, 0, 0)).with_active(Player::P2).key()
+        );
+        assert_ne!(key, state(Point::new(0, 0, 0)).flip_perspective().key());
+    }
+
+    #[test]
+    fn test_position_string() {
+        let state = State::default();
+        assert_eq!(state.to_position_string(), "w 0 Q1B2A3G3S2 Q1B2A3G3S2 -");
+        assert_eq!(
+            State::from_position_string(&state.to_position_string()),
+            Ok(state)
+        );
+
+        let state = State {
+            turn: 3,
+            active: Player::P2,
+            queens: [Some(Point::new(0, 0, 0)), Some(Point::new(1, 0, 0))],
+            unplaced: Pieces::from_counts(&HashMap::from([(PieceKind::Ant, 2)]), Player::P2),
+            board: Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+                (Point::new(1, 0, 0), Piece::Beetle(Player::P1)),
+                (Point::new(1, 0, 0), Piece::Beetle(Player::P2)),
+            ]),
+            ..Default::default()
+        };
+        let s = state.to_position_string();
+
+        assert_eq!(s, "b 3 - A2 0,0,0:wQ;1,0,0:bQwBbB");
+        assert_eq!(State::from_position_string(&s), Ok(state));
+    }
+
+    #[test]
+    fn test_position_string_errors() {
+        for s in [
+            "",
+            "w 0 - -",
+            "x 0 - - -",
+            "w -1 - - -",
+            "w 0 Q - -",
+            "w 0 X1 - -",
+            "w 0 - - 0,0,0:",
+            "w 0 - - 0,0:wQ",
+            "w 0 - - 0,0,0:wQb",
+            "w 0 - - 0,0,0:wQ;0,0,0:bQ",
+        ] {
+            assert_eq!(
+                State::from_position_string(s),
+                Err(HiveError::InvalidNotation(s.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_canonical() {
+        let state = State::default()
+            .apply(Move::Place {
+                kind: PieceKind::Spider,
+                to: Point::new(0, 0, 0),
+            })
+            .unwrap()
+            .apply(Move::Place {
+                kind: PieceKind::Spider,
+                to: Point::new(0, 0, 1),
+            })
+            .unwrap();
+        let (moved, _) =
+            state.transformed(|p| p.rotate60(1).reflect(Axis::X) + Point::new(3, 1, 0));
+
+        assert_ne!(moved, state);
+        assert_eq!(moved.canonical(), state.canonical());
+        assert_eq!(state.canonical().canonical(), state.canonical());
+    }
+
+    #[test]
+    fn test_all_reachable_positions() {
+        let state = State::default();
+
+        assert_eq!(state.all_reachable_positions(0).len(), 1);
+        // one opening placement per kind, then every reply touches the first piece the same way
+        // up to symmetry
+        assert_eq!(state.all_reachable_positions(1).len(), 1 + 5);
+        assert_eq!(state.all_reachable_positions(2).len(), 1 + 5 + 5 * 5);
+        let three = state.all_reachable_positions(3);
+        assert!(three.len() < state.perft(3) as usize);
+        assert!(three.iter().all(|state| *state == state.canonical()));
     }
 
-    pub fn next_turn(&self, queen: Option<Point>, unplaced: Option<Pieces>, board: Board) -> Self {
-        Self {
-            turn: if self.active == Player::P2 {
-                self.turn + 1
-            } else {
-                self.turn
-            },
-            active: !self.active,
-            p1_queen: if self.active == Player::P1 && queen.is_some() {
-                queen
-            } else {
-                self.p1_queen
-            },
-            p2_queen: if self.active == Player::P2 && queen.is_some() {
-                queen
-            } else {
-                self.p2_queen
-            },
-            unplaced: unplaced.unwrap_or_else(|| self.unplaced.clone()),
-            board,
+    #[test]
+    fn test_canonical_moves() {
+        let state = State::default();
+        assert_eq!(state.canonical_moves().len(), 5);
+
+        let state = state
+            .apply(Move::Place {
+                kind: PieceKind::Grasshopper,
+                to: Point::new(0, 0, 0),
+            })
+            .unwrap()
+            .apply(Move::Place {
+                kind: PieceKind::Spider,
+                to: Point::new(0, 0, 1),
+            })
+            .unwrap();
+
+        // of the three hexes P1 can place on, the two either side of the line through both
+        // pieces are mirror images, and the grasshopper's one jump stays on that line
+        assert_eq!(state.successors().len(), 16);
+        assert_eq!(state.canonical_moves().len(), 11);
+    }
+
+    #[test]
+    fn test_game_undo() {
+        let mut game = Game::default();
+        let mut played = Vec::new();
+        for _ in 0..6 {
+            let mv = game
+                .state()
+                .successors()
+                .into_iter()
+                .map(|(mv, _)| mv)
+                .min()
+                .unwrap();
+            game.play(mv).unwrap();
+            played.push(mv);
+        }
+        assert_eq!(game.history().len(), 6);
+
+        while let Some(mv) = game.undo() {
+            assert_eq!(played.pop(), Some(mv));
         }
+
+        assert!(played.is_empty());
+        assert_eq!(game, Game::default());
     }
 
-    pub fn placeable_points(&self) -> Vec<Point> {
-        self.board
-            .iter()
-            .filter(|(_, piece)| piece.player() == self.active)
-            .filter(|(point, _)| {
-                point.neighbors().iter().all(|p| {
-                    self.board
-                        .get(p)
-                        .is_none_or(|piece| piece.player() == self.active)
+    #[test]
+    fn test_repetitions() {
+        let (white, black) = (Point::new(-1, 0, 0), Point::new(2, 0, 0));
+        let state = snapshot(
+            Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+                (white, Piece::Ant(Player::P1)),
+                (black, Piece::Ant(Player::P2)),
+            ]),
+            Point::new(0, 0, 0),
+            Point::new(1, 0, 0),
+        );
+        let mut game = Game::new(state.clone());
+        // somewhere touching only the queens, so neither ant ends up pinning the other
+        let shuffle = |game: &mut Game, from: Point| {
+            let to = game
+                .state()
+                .legal_destinations(from)
+                .into_iter()
+                .filter(|to| {
+                    to.neighbors()
+                        .iter()
+                        .filter_map(|p| game.state().board.top(p))
+                        .all(|piece| piece.kind() == PieceKind::Queen)
                 })
+                .filter(|to| ![white, black].contains(to))
+                .min()
+                .unwrap();
+            game.play(Move::Relocate {
+                kind: PieceKind::Ant,
+                from,
+                to,
             })
-            .map(|(&p, _)| p)
-            .collect_vec()
-    }
-
-    fn component_size(&self, point: Option<Point>) -> usize {
-        let Some(point) = point else {
-            return 0;
+            .unwrap();
+            to
         };
-        let mut q = VecDeque::new();
-        let mut visited = HashSet::new();
-        q.push_back(point);
-        visited.insert(point);
-        while let Some(point) = q.pop_front() {
-            point
-                .neighbors()
-                .into_iter()
-                .filter(|p| self.board.contains_key(p) && visited.insert(*p))
-                .for_each(|p| q.push_back(p));
+
+        // both ants step out and back again
+        let white_out = shuffle(&mut game, white);
+        let black_out = shuffle(&mut game, black);
+        assert_eq!(game.repetitions(), 1);
+        for (from, to) in [(white_out, white), (black_out, black)] {
+            game.play(Move::Relocate {
+                kind: PieceKind::Ant,
+                from,
+                to,
+            })
+            .unwrap();
         }
-        visited.len()
+
+        assert_eq!(game.state().turn, state.turn + 2);
+        assert_ne!(game.state(), &state);
+        assert_ne!(game.state().key(), state.key());
+        assert_eq!(game.state().position_key(), state.position_key());
+        assert_eq!(game.repetitions(), 2);
+        assert_ne!(
+            state.position_key(),
+            state.clone().with_active(Player::P2).position_key()
+        );
     }
 
-    pub fn validate(&self) -> bool {
-        self.component_size(self.board.keys().nth(0).cloned()) == self.board.len()
-            && match (self.turn, self.active, self.p1_queen, self.p2_queen) {
-                (5.., _, Some(_), Some(_)) => true,
-                (4, Player::P2, None, _) | (5.., _, _, _) => false,
-                _ => true,
+    #[test]
+    fn test_canonicalize() {
+        let zero = Point { x: 1, y: -1, z: 1 };
+        let shifted = |p: Point, k: isize| Point {
+            x: p.x + k * zero.x,
+            y: p.y + k * zero.y,
+            z: p.z + k * zero.z,
+        };
+        let norm = |p: Point| p.x.abs() + p.y.abs() + p.z.abs();
+
+        let mut rng = SmallRng::seed_from_u64(0);
+        let grid = (-4..=4)
+            .cartesian_product(-4..=4)
+            .cartesian_product(-4..=4)
+            .map(|((x, y), z)| (x, y, z));
+        let random = (0..1000).map(|_| {
+            let mut coord = || rng.random_range(-1_000_000..=1_000_000i64) as isize;
+            (coord(), coord(), coord())
+        });
+        for (x, y, z) in grid.chain(random) {
+            let p = Point { x, y, z };
+            let canonical = p.canonicalize();
+
+            assert_eq!(canonical.canonicalize(), canonical, "{p:?}");
+            assert_eq!(Point::new(x, y, z), canonical);
+            // still the same hex, written as briefly as possible
+            assert_eq!(canonical.axial(), p.axial());
+            let mut coords = [-canonical.x, canonical.y, -canonical.z];
+            coords.sort();
+            assert_eq!(coords[1], 0, "{p:?}");
+            for k in [-3, -2, -1, 1, 2, 3] {
+                assert!(norm(shifted(canonical, k)) > norm(canonical), "{p:?}");
+                assert_eq!(shifted(p, k).canonicalize(), canonical, "{p:?}");
             }
+        }
+
+        // different hexes never collapse together
+        let hexes = Point::new(0, 0, 0).spiral(4);
+        assert_eq!(hexes.iter().distinct().count(), hexes.len());
+        assert_eq!(
+            hexes.iter().map(|p| p.axial()).distinct().count(),
+            hexes.len()
+        );
     }
 
-    pub fn get_moves(&self) -> HashSet<State> {
-        let mut v = HashSet::new();
-        v.extend(
-            (0..match self.active {
-                Player::P1 => &self.unplaced.p1,
-                Player::P2 => &self.unplaced.p2,
-            }
-            .len())
-                .cartesian_product(match (self.board.len(), self.active) {
-                    (0..=1, Player::P1) => vec![Point::new(0, 0, 0)],
-                    (0..=1, Player::P2) => vec![Point::new(0, 0, 1)],
-                    _ => self.placeable_points(),
-                })
-                .map(|(idx, point)| {
-                    let mut b = self.board.clone();
-                    let mut pieces = self.unplaced.clone();
-                    b.insert(point, pieces.remove(self.active, idx));
-                    self.next_turn(
-                        if let Piece::Queen(_) = b[&point] {
-                            Some(point)
-                        } else {
-                            None
-                        },
-                        Some(pieces),
-                        b,
-                    )
-                }),
-        );
-        for (point, piece) in self
-            .board
-            .iter()
-            .filter(|&(_, piece)| piece.player() == self.active)
-        {
-            v.extend(
-                match piece {
-                    Piece::Queen(_) => point
-                        .movable_neighbors(&self.board)
-                        .map(|p| {
-                            let mut b = self.board.clone();
-                            let piece = b.remove(point).unwrap();
-                            b.insert(p, piece);
-                            (b, Some(p))
-                        })
-                        .collect_vec(),
-                    Piece::Beetle(player, under) => point
-                        .neighbors()
-                        .into_iter()
-                        .map(|p| {
-                            let mut b = self.board.clone();
-                            let piece = b.remove(point).map(Box::new);
-                            b.insert(p, Piece::Beetle(*player, piece));
-                            if let Some(u) = under {
-                                b.insert(*point, *u.clone());
-                            }
-                            (b, None)
-                        })
-                        .collect_vec(),
-                    Piece::Ant(_) => {
-                        fn ant_moves(
-                            point: Point,
-                            original_board: &Board,
-                            hypothetical_board: &Board,
-                            visited: &mut HashSet<Point>,
-                        ) -> Vec<Board> {
-                            point
-                                .movable_neighbors(hypothetical_board)
-                                .filter(|neighbor| {
-                                    neighbor
-                                        .neighbors()
-                                        .into_iter()
-                                        .any(|p| original_board.get(&p).is_some())
-                                        && visited.insert(*neighbor)
-                                })
-                                // HACK: consume the iterator so that visited isn't borrowed mutably more
-                                // than once
-                                .collect_vec()
-                                .into_iter()
-                                .flat_map(|p| {
-                                    let mut b = hypothetical_board.clone();
-                                    let e = b.remove(&point).unwrap();
-                                    b.insert(p, e);
-                                    ant_moves(p, original_board, &b, visited)
-                                        .into_iter()
-                                        .chain(once(b))
-                                        .collect_vec()
-                                })
-                                .collect_vec()
-                        }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_point_serde() {
+        for p in Point::new(0, 0, 0).spiral(3) {
+            let json = serde_json::to_string(&p).unwrap();
+            assert_eq!(serde_json::from_str::<Point>(&json).unwrap(), p);
+        }
+        assert_eq!(
+            serde_json::to_string(&Point::new(0, 0, 1)).unwrap(),
+            "[0,1]"
+        );
+        // any spelling of the hex comes back canonical
+        let long = Point { x: 2, y: -1, z: 2 };
+        let json = serde_json::to_string(&long).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Point>(&json).unwrap(),
+            long.canonicalize()
+        );
+        assert!(serde_json::from_str::<Point>("[1,2,3]").is_err());
+    }
 
-                        let mut cache = HashSet::new();
-                        ant_moves(*point, &self.board, &self.board, &mut cache)
-                            .into_iter()
-                            .map(|b| (b, None))
-                            .collect_vec()
-                    }
-                    Piece::Grasshopper(_) => vec![-1, 1]
-                        .into_iter()
-                        .cartesian_product(0..3)
-                        .map(|(dir, axis)| {
-                            let mut p = *point;
-                            while self.board.contains_key(&p) {
-                                p[axis] += dir;
-                            }
-                            let mut b = self.board.clone();
-                            let piece = b.remove(point).unwrap();
-                            b.insert(p, piece);
-                            (b, None)
-                        })
-                        .collect_vec(),
-                    Piece::Spider(_) => {
-                        fn spider_moves(
-                            point: Point,
-                            board: &Board,
-                            visited: &mut HashSet<Point>,
-                            moves_remaining: usize,
-                        ) -> Vec<Board> {
-                            if moves_remaining > 0 {
-                                point
-                                    .movable_neighbors(board)
-                                    .filter(|p| {
-                                        p.neighbors()
-                                            .into_iter()
-                                            .any(|p| board.get(&p).is_some() && visited.insert(p))
-                                    })
-                                    // HACK: consume the iterator so that visited isn't borrowed mutably more
-                                    // than once
-                                    .collect_vec()
-                                    .into_iter()
-                                    .flat_map(|p| {
-                                        let mut b = board.clone();
-                                        let e = b.remove(&point).unwrap();
-                                        b.insert(p, e);
-                                        p.movable_neighbors(&b)
-                                            .flat_map(|neighbor| {
-                                                spider_moves(
-                                                    neighbor,
-                                                    &b,
-                                                    visited,
-                                                    moves_remaining - 1,
-                                                )
-                                            })
-                                            .collect_vec()
-                                            .into_iter()
-                                            .chain(once(b))
-                                            .collect_vec()
-                                    })
-                                    .collect_vec()
-                            } else {
-                                Vec::new()
-                            }
-                        }
+    #[test]
+    fn test_points() {
+        assert!(PieceKind::Ant.points() > PieceKind::Spider.points());
+        assert!(PieceKind::Ant.points() > PieceKind::Beetle.points());
+        assert!(PieceKind::Beetle.points() > PieceKind::Grasshopper.points());
+        assert_eq!(PieceKind::Queen.points(), 0);
+        assert!(
+            PieceKind::ALL
+                .iter()
+                .all(|&kind| PieceWeights::default().weight(kind) == kind.points())
+        );
+    }
 
-                        let mut cache = HashSet::new();
-                        spider_moves(*point, &self.board, &mut cache, 3)
-                            .into_iter()
-                            .map(|b| (b, None))
-                            .collect_vec()
-                    }
-                }
-                .into_iter()
-                .map(|(b, queen)| self.next_turn(queen, None, b)),
+    #[test]
+    fn test_count_surrounding() {
+        let origin = Point::new(0, 0, 0);
+        let mut board = Board::default();
+        for (i, &p) in origin.neighbors().iter().enumerate() {
+            board.place(
+                p,
+                Piece::Ant(if i % 2 == 0 { Player::P1 } else { Player::P2 }),
             );
         }
+        let state = State::default().with_board(board.clone());
 
-        v.into_iter().filter(|s| s.validate()).collect()
+        assert_eq!(board.count_surrounding(origin), 6);
+        assert_eq!(state.count_surrounding(origin), 6);
+        assert_eq!(board.count_surrounding(Point::new(1, 0, 0)), 2);
+        assert_eq!(board.count_surrounding(Point::new(2, 0, 0)), 1);
+        assert_eq!(board.count_surrounding(Point::new(5, 0, 0)), 0);
+
+        board.place(origin, Piece::Queen(Player::P1));
+        board.place(Point::new(1, 0, 0), Piece::Beetle(Player::P2));
+        assert_eq!(board.count_surrounding(origin), 6);
+        assert_eq!(board.count_surrounding(Point::new(1, 0, 0)), 3);
+        assert_eq!(board.count_surrounding(Point::new(2, 0, 0)), 1);
     }
-}
 
-impl Default for State {
-    fn default() -> Self {
-        Self {
-            turn: 0,
-            active: Player::P1,
-            p1_queen: None,
-            p2_queen: None,
-            unplaced: Pieces::default(),
-            board: Board::default(),
+    #[test]
+    fn test_ply() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut state = State::default();
+
+        for ply in 0..20 {
+            assert_eq!(state.ply(), ply);
+            state = state.apply(state.random_move(&mut rng)).unwrap();
         }
+        assert_eq!(state.turn, 10);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_opening_phase() {
+        let mut state = State::default();
+        let mut phases = Vec::new();
+        for (kind, to) in [
+            (PieceKind::Spider, Point::new(0, 0, 0)),
+            (PieceKind::Spider, Point::new(1, 0, 0)),
+            (PieceKind::Queen, Point::new(-1, 0, 0)),
+            (PieceKind::Queen, Point::new(2, 0, 0)),
+            (PieceKind::Ant, Point::new(-2, 0, 0)),
+        ] {
+            phases.push((state.is_first_move(), state.is_opening()));
+            state = state.place(kind, to).unwrap();
+        }
+        phases.push((state.is_first_move(), state.is_opening()));
+
+        assert_eq!(
+            phases,
+            [
+                (true, true),
+                (true, true),
+                (false, true),
+                (false, true),
+                (false, false),
+                (false, false),
+            ]
+        );
+        // it's about what's on the board, not whose turn it nominally is
+        let first = State::default()
+            .place(PieceKind::Spider, Point::new(0, 0, 0))
+            .unwrap();
+        assert!(first.is_first_move());
+        assert!(!first.with_active(Player::P1).is_first_move());
+    }
 
     #[test]
-    fn test_first_move() {
-        let state = State::default();
+    fn test_place_canonicalizes() {
+        // (1, 0, 1) is (0, 1, 0) written the long way
+        let long = Point { x: 1, y: 0, z: 1 };
+        let canonical = Point::new(0, 1, 0);
+        let mut board = Board::default();
+        board.place(long, Piece::Queen(Player::P1));
+        board.place(canonical, Piece::Beetle(Player::P2));
 
-        let moves = state.get_moves();
+        assert_eq!(board.keys().collect_vec(), [&canonical]);
+        assert_eq!(board.top(&canonical), Some(&Piece::Beetle(Player::P2)));
+        assert_eq!(board[&canonical].height(), 2);
+        assert!(!board.contains_key(&long));
+        assert_eq!(
+            board,
+            Board::from([
+                (canonical, Piece::Queen(Player::P1)),
+                (long, Piece::Beetle(Player::P2)),
+            ])
+        );
 
-        assert_eq!(moves.len(), 5);
+        let state = State::new(
+            None,
+            Player::P1,
+            Some(long),
+            None,
+            Pieces::new(),
+            Board::from([(long, Piece::Queen(Player::P1))]),
+        );
+        assert!(state.is_queen_placed(Player::P1));
+        assert_eq!(state.queen(Player::P1), Some(canonical));
+        assert_eq!(
+            state
+                .clone()
+                .with_queens(Some(long), None)
+                .queen(Player::P1),
+            Some(canonical)
+        );
+
+        // bypassing place is still caught
+        let mut raw = Board::default();
+        raw.insert(long, Stack::from(Piece::Queen(Player::P1)));
+        assert_eq!(
+            state.with_board(raw).validate_full(&Pieces::new()),
+            Err(HiveError::NonCanonical(long))
+        );
     }
 
     #[test]
-    fn test_second_move() {
-        for state in State::default().get_moves().into_iter() {
-            let moves = state.get_moves();
+    fn test_piece_positions() {
+        let mut board = Board::from([
+            (Point::new(0, 0, 0), Piece::Ant(Player::P1)),
+            (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+            (Point::new(2, 0, 0), Piece::Ant(Player::P2)),
+            (Point::new(-1, 0, 0), Piece::Ant(Player::P1)),
+            (Point::new(3, 0, 0), Piece::Spider(Player::P2)),
+        ]);
+        // a covered ant still counts
+        board.place(Point::new(2, 0, 0), Piece::Beetle(Player::P1));
+
+        assert_eq!(
+            board.piece_positions(PieceKind::Ant),
+            vec![
+                Point::new(-1, 0, 0),
+                Point::new(0, 0, 0),
+                Point::new(2, 0, 0)
+            ]
+        );
+        assert_eq!(
+            board.piece_positions_of(PieceKind::Ant, Player::P1),
+            vec![Point::new(-1, 0, 0), Point::new(0, 0, 0)]
+        );
+        assert_eq!(
+            board.piece_positions_of(PieceKind::Ant, Player::P2),
+            vec![Point::new(2, 0, 0)]
+        );
+        assert_eq!(
+            board.piece_positions(PieceKind::Beetle),
+            vec![Point::new(2, 0, 0)]
+        );
+        assert!(board.piece_positions(PieceKind::Mosquito).is_empty());
+    }
+
+    #[test]
+    fn test_empty_neighbors() {
+        let point = Point::new(1, 0, 0);
+        let neighbors = point.neighbors();
+        let mut board = Board::from([
+            (point, Piece::Queen(Player::P1)),
+            (neighbors[Direction::W as usize], Piece::Ant(Player::P2)),
+            (neighbors[Direction::NE as usize], Piece::Spider(Player::P1)),
+        ]);
+        board.place(neighbors[Direction::NE as usize], Piece::Beetle(Player::P2));
+
+        let empty = board.empty_neighbors(point).collect_vec();
+        assert_eq!(
+            empty,
+            [Direction::SW, Direction::SE, Direction::E, Direction::NW]
+                .map(|direction| neighbors[direction as usize])
+        );
+        assert_eq!(empty.len() + board.count_surrounding(point), 6);
+        assert_eq!(board.empty_neighbors(Point::new(9, 0, 0)).count(), 6);
+    }
+
+    #[test]
+    fn test_queen_ring() {
+        let queen = Point::new(1, 0, 0);
+        let neighbors = queen.neighbors();
+        let mut board = Board::from([
+            (queen, Piece::Queen(Player::P2)),
+            (neighbors[Direction::W as usize], Piece::Queen(Player::P1)),
+            (neighbors[Direction::NE as usize], Piece::Ant(Player::P1)),
+            (neighbors[Direction::SE as usize], Piece::Spider(Player::P2)),
+        ]);
+        board.place(neighbors[Direction::SE as usize], Piece::Beetle(Player::P1));
+        let state = State::default()
+            .with_queens(Some(neighbors[Direction::W as usize]), Some(queen))
+            .with_board(board);
+
+        assert_eq!(
+            state.queen_ring(Player::P2),
+            Some([
+                Some(Piece::Queen(Player::P1)),
+                None,
+                Some(Piece::Beetle(Player::P1)),
+                None,
+                Some(Piece::Ant(Player::P1)),
+                None,
+            ])
+        );
+        assert_eq!(
+            state
+                .queen_ring(Player::P2)
+                .unwrap()
+                .iter()
+                .flatten()
+                .count(),
+            6 - state.liberties(Player::P2).unwrap()
+        );
+        assert_eq!(
+            state.queen_ring(Player::P1).unwrap()[Direction::E as usize],
+            Some(Piece::Queen(Player::P2))
+        );
+        assert_eq!(State::default().queen_ring(Player::P1), None);
+    }
+
+    #[test]
+    fn test_glyphs() {
+        let glyphs = PieceKind::ALL.map(|kind| (kind.ascii(), kind.unicode()));
 
-            assert_eq!(moves.len(), 5);
+        assert_eq!(
+            glyphs,
+            [
+                ('Q', '🐝'),
+                ('B', '🪲'),
+                ('A', '🐜'),
+                ('G', '🦗'),
+                ('S', '🕷'),
+                ('M', '🦟'),
+            ]
+        );
+        assert_eq!(glyphs.iter().map(|(a, _)| a).unique().count(), glyphs.len());
+        assert!(glyphs.iter().all(|(a, _)| a.is_ascii_uppercase()));
+        for kind in PieceKind::ALL {
+            assert_eq!(PieceKind::from_letter(kind.ascii()), Some(kind));
         }
+        assert_eq!(Piece::Queen(Player::P1).ascii(), "wQ");
+        assert_eq!(Piece::Mosquito(Player::P2).ascii(), "bM");
+        assert_eq!(Piece::Ant(Player::P1).unicode(), "w🐜");
+        assert_eq!(Piece::Spider(Player::P2).unicode(), "b🕷");
     }
 
     #[test]
-    fn test_validate_no_queen() {
+    fn test_validate_full() {
+        let state = State::default()
+            .apply(Move::Place {
+                kind: PieceKind::Queen,
+                to: Point::new(0, 0, 0),
+            })
+            .unwrap()
+            .apply(Move::Place {
+                kind: PieceKind::Beetle,
+                to: Point::new(0, 0, 1),
+            })
+            .unwrap();
+        assert_eq!(state.validate_full(&Pieces::new()), Ok(()));
+        assert_eq!(State::default().validate_full(&Pieces::new()), Ok(()));
+
+        // (1, -1, 0) is the hex at (0, 0, -1)
+        let mut point = Point::new(0, 0, 0);
+        point[0] += 1;
+        point[1] -= 1;
+        let mut board = state.board.clone();
+        board.insert(point, Stack::from(Piece::Ant(Player::P1)));
+        assert_eq!(
+            state
+                .clone()
+                .with_board(board)
+                .validate_full(&Pieces::new()),
+            Err(HiveError::NonCanonical(point))
+        );
+
+        let mut board = state.board.clone();
+        board.place(Point::new(0, 0, 1), Piece::Ant(Player::P1));
+        assert_eq!(
+            state
+                .clone()
+                .with_board(board)
+                .validate_full(&Pieces::new()),
+            Err(HiveError::InvalidStack(Point::new(0, 0, 1)))
+        );
+
+        let mut board = state.board.clone();
+        board.move_top(Point::new(0, 0, 1), Point::new(0, 0, 3));
+        assert_eq!(
+            state
+                .clone()
+                .with_board(board)
+                .validate_full(&Pieces::new()),
+            Err(HiveError::Disconnected)
+        );
+
+        assert_eq!(
+            state
+                .clone()
+                .with_queens(Some(Point::new(0, 0, 1)), None)
+                .validate_full(&Pieces::new()),
+            Err(HiveError::QueenMismatch(Player::P1))
+        );
+        let mut board = state.board.clone();
+        board.place(Point::new(1, 0, 0), Piece::Queen(Player::P2));
+        assert_eq!(
+            state
+                .clone()
+                .with_board(board)
+                .validate_full(&Pieces::new()),
+            Err(HiveError::QueenMismatch(Player::P2))
+        );
+
+        assert_eq!(
+            state
+                .clone()
+                .with_unplaced(Pieces::new())
+                .validate_full(&Pieces::new()),
+            Err(HiveError::PieceCount(Player::P1, PieceKind::Queen))
+        );
+        // the beetle P2 placed is back in their hand too
+        let counts = PieceKind::ALL
+            .into_iter()
+            .map(|kind| {
+                (
+                    kind,
+                    Pieces::new().p2.iter().filter(|p| p.kind() == kind).count(),
+                )
+            })
+            .collect();
+        let unplaced = state.unplaced.clone().with_counts(&counts, Player::P2);
+        assert_eq!(
+            state.with_unplaced(unplaced).validate_full(&Pieces::new()),
+            Err(HiveError::PieceCount(Player::P2, PieceKind::Beetle))
+        );
+
+        // a variant hand with a mosquito checks against that hand, not the base set
+        let counts = HashMap::from([
+            (PieceKind::Queen, 1),
+            (PieceKind::Ant, 2),
+            (PieceKind::Mosquito, 1),
+        ]);
+        let full = Pieces::from_counts(&counts, Player::P1).with_counts(&counts, Player::P2);
+        let state = State::default()
+            .with_unplaced(full.clone())
+            .place(PieceKind::Mosquito, Point::new(0, 0, 0))
+            .unwrap()
+            .place(PieceKind::Queen, Point::new(1, 0, 0))
+            .unwrap()
+            .place(PieceKind::Queen, Point::new(-1, 0, 0))
+            .unwrap();
+        assert_eq!(state.validate_full(&full), Ok(()));
+        assert_eq!(
+            state.validate_full(&Pieces::new()),
+            Err(HiveError::PieceCount(Player::P1, PieceKind::Beetle))
+        );
+    }
+
+    #[test]
+    fn test_threat_moves_next_turn() {
+        // P1's queen has two liberties, and P2's grasshopper can jump into one of them
+        let origin = Point::new(0, 0, 0);
+        let [w, sw, se, e, ne, _] = origin.neighbors()[..] else {
+            unreachable!()
+        };
+        let grasshopper = Point::new(0, -2, 0);
+        let state = State::default()
+            .with_turn(6)
+            .with_queens(Some(origin), Some(se))
+            .with_unplaced(
+                Pieces::from_counts(&HashMap::new(), Player::P1)
+                    .with_counts(&HashMap::new(), Player::P2),
+            )
+            .with_board(Board::from([
+                (origin, Piece::Queen(Player::P1)),
+                (w, Piece::Ant(Player::P1)),
+                (sw, Piece::Spider(Player::P1)),
+                (se, Piece::Queen(Player::P2)),
+                (e, Piece::Beetle(Player::P1)),
+                (grasshopper, Piece::Grasshopper(Player::P2)),
+            ]));
+
+        assert_eq!(state.liberties(Player::P1), Some(2));
+        assert_eq!(
+            state.threat_moves_next_turn(),
+            vec![Move::Relocate {
+                kind: PieceKind::Grasshopper,
+                from: grasshopper,
+                to: ne,
+            }]
+        );
+        assert!(
+            state
+                .clone()
+                .with_queens(None, Some(se))
+                .threat_moves_next_turn()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_queen_defending_moves() {
+        // P1's ant is hemming in its own queen and can walk off anywhere around the hive
+        let origin = Point::new(0, 0, 0);
+        let [w, sw, se, e, ne, _] = origin.neighbors()[..] else {
+            unreachable!()
+        };
+        let state = State::default()
+            .with_turn(6)
+            .with_queens(Some(origin), Some(se))
+            .with_unplaced(
+                Pieces::from_counts(&HashMap::new(), Player::P1)
+                    .with_counts(&HashMap::new(), Player::P2),
+            )
+            .with_board(Board::from([
+                (origin, Piece::Queen(Player::P1)),
+                (w, Piece::Ant(Player::P1)),
+                (sw, Piece::Spider(Player::P1)),
+                (se, Piece::Queen(Player::P2)),
+                (e, Piece::Beetle(Player::P1)),
+                (Point::new(0, -2, 0), Piece::Grasshopper(Player::P2)),
+            ]));
+
+        let defending = state.queen_defending_moves();
+
+        // walking the ant off frees one hex; the queen stepping out into the open can free more
+        let ant_moves = state.legal_moves_for(w);
+        assert!(ant_moves.iter().any(|mv| defending.contains(mv)));
+        assert_eq!(defending[0].piece_kind(), Some(PieceKind::Queen));
+        for mv in &defending {
+            let after = state.apply(*mv).unwrap().liberties(Player::P1).unwrap();
+            assert!(after >= state.liberties(Player::P1).unwrap());
+        }
+        // swapping the ant into the hex the grasshopper could jump to keeps the same liberties
+        // but takes that threat away
+        let block = Move::Relocate {
+            kind: PieceKind::Ant,
+            from: w,
+            to: ne,
+        };
+        assert!(defending.contains(&block));
+        assert!(
+            defending.iter().position(|mv| ant_moves.contains(mv))
+                < defending.iter().position(|mv| *mv == block)
+        );
+        assert!(
+            state
+                .clone()
+                .with_queens(None, Some(se))
+                .queen_defending_moves()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_place() {
+        let state = State::default()
+            .place(PieceKind::Spider, Point::new(0, 0, 0))
+            .unwrap()
+            .place(PieceKind::Ant, Point::new(1, 0, 0))
+            .unwrap();
+
+        let placed = state.place(PieceKind::Queen, Point::new(-1, 0, 0)).unwrap();
+        assert_eq!(
+            Ok(placed),
+            state.apply(Move::Place {
+                kind: PieceKind::Queen,
+                to: Point::new(-1, 0, 0),
+            })
+        );
+        assert_eq!(
+            state.place(PieceKind::Queen, Point::new(2, 0, 0)),
+            Err(HiveError::NotPlaceable(Point::new(2, 0, 0)))
+        );
+        assert_eq!(
+            state.place(PieceKind::Queen, Point::new(-3, 0, 0)),
+            Err(HiveError::NotPlaceable(Point::new(-3, 0, 0)))
+        );
+        assert_eq!(
+            state.place(PieceKind::Queen, Point::new(1, 0, 0)),
+            Err(HiveError::Occupied(Point::new(1, 0, 0)))
+        );
+
+        let no_queen = state.clone().with_unplaced(
+            Pieces::new().with_counts(&HashMap::from([(PieceKind::Queen, 0)]), Player::P1),
+        );
+        assert_eq!(
+            no_queen.place(PieceKind::Queen, Point::new(-1, 0, 0)),
+            Err(HiveError::NotInHand(Player::P1, PieceKind::Queen))
+        );
+
+        let late = state.with_turn(3);
+        assert_eq!(
+            late.place(PieceKind::Ant, Point::new(-1, 0, 0)),
+            Err(HiveError::QueenRequired(Player::P1))
+        );
+        assert!(late.place(PieceKind::Queen, Point::new(-1, 0, 0)).is_ok());
+    }
+
+    #[test]
+    fn test_move_piece() {
+        let state = snapshot(
+            Board::from([
+                (Point::new(-1, 0, 0), Piece::Spider(Player::P1)),
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+                (Point::new(2, 0, 0), Piece::Ant(Player::P1)),
+            ]),
+            Point::new(0, 0, 0),
+            Point::new(1, 0, 0),
+        );
+
+        let moved = state
+            .move_piece(Point::new(2, 0, 0), Point::new(-2, 0, 0))
+            .unwrap();
+        assert_eq!(
+            Ok(moved),
+            state.apply(Move::Relocate {
+                kind: PieceKind::Ant,
+                from: Point::new(2, 0, 0),
+                to: Point::new(-2, 0, 0),
+            })
+        );
+        // one step is two short of a spider's walk
+        assert_eq!(
+            state.move_piece(Point::new(-1, 0, 0), Point::new(-2, 0, 0)),
+            Err(HiveError::IllegalMove(Move::Relocate {
+                kind: PieceKind::Spider,
+                from: Point::new(-1, 0, 0),
+                to: Point::new(-2, 0, 0),
+            }))
+        );
+        assert_eq!(
+            state.move_piece(Point::new(0, 0, 0), Point::new(0, 1, 0)),
+            Err(HiveError::Pinned(Point::new(0, 0, 0)))
+        );
+        assert_eq!(
+            state.move_piece(Point::new(1, 0, 0), Point::new(1, 1, 0)),
+            Err(HiveError::WrongPlayer(Player::P2))
+        );
+        assert_eq!(
+            state.move_piece(Point::new(5, 0, 0), Point::new(-2, 0, 0)),
+            Err(HiveError::Empty(Point::new(5, 0, 0)))
+        );
+
+        let mut covered = state.board.clone();
+        covered.place(Point::new(2, 0, 0), Piece::Beetle(Player::P2));
+        assert_eq!(
+            state
+                .with_board(covered)
+                .move_piece(Point::new(2, 0, 0), Point::new(-2, 0, 0)),
+            Err(HiveError::Pinned(Point::new(2, 0, 0)))
+        );
+    }
+
+    #[test]
+    fn test_placement_tracks_queens() {
+        let state = State::default()
+            .place(PieceKind::Queen, Point::new(0, 0, 0))
+            .unwrap();
+        assert_eq!(state.queens, [Some(Point::new(0, 0, 0)), None]);
+
+        let state = state.place(PieceKind::Queen, Point::new(1, 0, 0)).unwrap();
+        assert_eq!(
+            state.queens,
+            [Some(Point::new(0, 0, 0)), Some(Point::new(1, 0, 0))]
+        );
+        for player in [Player::P1, Player::P2] {
+            assert_eq!(state.queen(player), state.queens[player.index()]);
+        }
+
+        // placing anything else leaves them be
+        let state = state.place(PieceKind::Ant, Point::new(-1, 0, 0)).unwrap();
+        assert_eq!(
+            state.queens,
+            [Some(Point::new(0, 0, 0)), Some(Point::new(1, 0, 0))]
+        );
+        assert_eq!(state.validate_full(&Pieces::new()), Ok(()));
+    }
+
+    #[test]
+    fn test_relocate_tracks_queens() {
+        let state = snapshot(
+            Board::from([
+                (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+                (Point::new(1, 0, 0), Piece::Queen(Player::P2)),
+                (Point::new(-1, 0, 0), Piece::Beetle(Player::P1)),
+            ]),
+            Point::new(0, 0, 0),
+            Point::new(1, 0, 0),
+        );
+
+        // the mover's own queen
+        let slid = state.relocate(Point::new(0, 0, 0), Point::new(0, -1, 0));
+        assert_eq!(slid.queen(Player::P1), Some(Point::new(0, -1, 0)));
+        assert_eq!(slid.queen(Player::P2), Some(Point::new(1, 0, 0)));
+
+        // the opponent's queen, as a pillbug throw would move her
+        let thrown = state.relocate(Point::new(1, 0, 0), Point::new(0, 0, 1));
+        assert_eq!(thrown.queen(Player::P1), Some(Point::new(0, 0, 0)));
+        assert_eq!(thrown.queen(Player::P2), Some(Point::new(0, 0, 1)));
+        assert!(thrown.validate());
+
+        // covering and uncovering a queen doesn't move her
+        let covered = state.relocate(Point::new(-1, 0, 0), Point::new(0, 0, 0));
+        assert_eq!(covered.queen(Player::P1), Some(Point::new(0, 0, 0)));
+        let uncovered = covered
+            .with_active(Player::P1)
+            .relocate(Point::new(0, 0, 0), Point::new(0, -1, 0));
+        assert_eq!(uncovered.queen(Player::P1), Some(Point::new(0, 0, 0)));
+        assert!(uncovered.validate());
+    }
+
+    #[test]
+    fn test_distance_to_loss() {
+        let queen = Point::new(0, 0, 0);
+        let mut board = Board::from([(queen, Piece::Queen(Player::P1))]);
+        let state = |board: &Board| {
+            State::default()
+                .with_queens(Some(queen), None)
+                .with_board(board.clone())
+        };
+
+        assert_eq!(state(&board).distance_to_loss(Player::P1), Some(6));
+        assert_eq!(state(&board).distance_to_loss(Player::P2), None);
+        for (i, p) in queen.neighbors().into_iter().enumerate() {
+            board.place(p, Piece::Ant(Player::P2));
+            let state = state(&board);
+            assert_eq!(state.distance_to_loss(Player::P1), Some(5 - i));
+            assert_eq!(
+                state.distance_to_loss(Player::P1),
+                state.liberties(Player::P1)
+            );
+        }
+        assert_eq!(state(&board).result(), GameResult::P2Wins);
+    }
+
+    #[test]
+    fn test_liberties_covered_queen() {
+        let queen = Point::new(0, 0, 0);
+        let mut board: Board = queen
+            .neighbors()
+            .into_iter()
+            .take(4)
+            .map(|p| (p, Piece::Ant(Player::P1)))
+            .collect();
+        board.place(queen, Piece::Queen(Player::P2));
+        board.place(queen, Piece::Beetle(Player::P1));
         let state = State {
-            turn: 5,
-            active: Player::P2,
+            turn: 4,
+            queens: [None, Some(queen)],
+            board,
             ..Default::default()
         };
 
-        assert!(!state.validate());
+        assert_eq!(state.liberties(Player::P2), Some(2));
+        assert_eq!(state.liberties(Player::P1), None);
+        assert_eq!(state.result(), GameResult::InProgress);
+
+        let mut board = state.board.clone();
+        let neighbors = queen.neighbors();
+        board.place(neighbors[4], Piece::Spider(Player::P1));
+        board.place(neighbors[5], Piece::Spider(Player::P2));
+        board.place(neighbors[5], Piece::Beetle(Player::P1));
+        let state = state.with_board(board);
+
+        assert_eq!(state.liberties(Player::P2), Some(0));
+        assert_eq!(state.result(), GameResult::P1Wins);
     }
 
     #[test]
-    fn test_validate_valid() {
-        let state = State::default();
+    fn test_retain_player() {
+        let board = Board::from([
+            (Point::new(0, 0, 0), Piece::Queen(Player::P1)),
+            (Point::new(1, 0, 0), Piece::Ant(Player::P1)),
+            (Point::new(0, 0, 1), Piece::Queen(Player::P2)),
+            (Point::new(0, 0, 2), Piece::Spider(Player::P1)),
+            (Point::new(0, 0, 2), Piece::Beetle(Player::P2)),
+            (Point::new(0, 0, 2), Piece::Beetle(Player::P1)),
+        ]);
 
-        assert!(state.validate());
+        let p1 = board.retain_player(Player::P1);
+        let p2 = board.retain_player(Player::P2);
+
+        assert_eq!(p1.len(), 3);
+        assert_eq!(
+            *p1[&Point::new(0, 0, 2)],
+            [Piece::Spider(Player::P1), Piece::Beetle(Player::P1)]
+        );
+        assert_eq!(
+            p2,
+            Board::from([
+                (Point::new(0, 0, 1), Piece::Queen(Player::P2)),
+                (Point::new(0, 0, 2), Piece::Beetle(Player::P2)),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_queen_in_danger() {
+        let queen = Point::new(0, 0, 0);
+        let state = |filled| State {
+            turn: 4,
+            queens: [Some(queen), None],
+            board: once((queen, Piece::Queen(Player::P1)))
+                .chain(
+                    queen
+                        .neighbors()
+                        .into_iter()
+                        .take(filled)
+                        .map(|p| (p, Piece::Ant(Player::P2))),
+                )
+                .collect(),
+            ..Default::default()
+        };
+
+        assert!(state(6).queen_in_danger(Player::P1));
+        assert!(state(5).queen_in_danger(Player::P1));
+        assert!(!state(4).queen_in_danger(Player::P1));
+        assert!(!state(6).queen_in_danger(Player::P2));
     }
 }
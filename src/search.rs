@@ -0,0 +1,501 @@
+#[cfg(feature = "std")]
+use alloc::vec;
+use alloc::vec::Vec;
+
+#[cfg(feature = "std")]
+use rand::{Rng, RngExt};
+
+use crate::{GameResult, HashMap, Move, Player, State, eval::Evaluator, hash_map};
+
+const WIN: i32 = 1_000_000;
+/// Below every score a search can return, losses included, and safe to negate.
+const FLOOR: i32 = -i32::MAX;
+/// Random playouts that run longer than this are scored as draws.
+#[cfg(feature = "std")]
+const PLAYOUT_PLIES: usize = 40;
+#[cfg(feature = "std")]
+const EXPLORATION: f64 = core::f64::consts::SQRT_2;
+
+pub trait OpeningBook {
+    fn lookup(&self, state: &State) -> Option<Move>;
+}
+
+/// A book keyed by [`State::canonical`], so a booked move is found from any translation,
+/// rotation or reflection of its position.
+#[derive(Debug, Clone, Default)]
+pub struct HashMapBook {
+    moves: HashMap<State, Move>,
+}
+
+impl HashMapBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Books `mv` for `state`, returning the move it replaces, if any, as played from `state`.
+    pub fn insert(&mut self, state: State, mv: Move) -> Option<Move> {
+        let (canonical, symmetry) = state.canonical_symmetry();
+        self.moves
+            .insert(canonical, mv.mapped(|p| symmetry.apply(p)))
+            .map(|mv| mv.mapped(|p| symmetry.invert(p)))
+    }
+}
+
+impl OpeningBook for HashMapBook {
+    fn lookup(&self, state: &State) -> Option<Move> {
+        let (canonical, symmetry) = state.canonical_symmetry();
+        self.moves
+            .get(&canonical)
+            .map(|mv| mv.mapped(|p| symmetry.invert(p)))
+    }
+}
+
+/// Memoizes [`State::legal_moves`] for positions that are asked about more than once. There's no
+/// Zobrist key to index by, so the cache is keyed by the position itself: its hash is a cheap
+/// fingerprint of the board, and a collision can't return the wrong moves.
+#[derive(Debug, Clone, Default)]
+pub struct MoveCache {
+    moves: HashMap<State, Vec<Move>>,
+    generated: usize,
+}
+
+impl MoveCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The legal moves in `state`, generating them only the first time `state` is seen.
+    pub fn legal_moves(&mut self, state: &State) -> &[Move] {
+        match self.moves.entry(state.clone()) {
+            hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            hash_map::Entry::Vacant(entry) => {
+                self.generated += 1;
+                entry.insert(state.legal_moves())
+            }
+        }
+    }
+
+    /// How many times moves have actually been generated, i.e. the number of cache misses.
+    pub fn generated(&self) -> usize {
+        self.generated
+    }
+
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.moves.clear();
+    }
+}
+
+/// Picks a move for the side to move with a fixed-depth negamax search, scoring the positions at
+/// the horizon with `eval`. A move found in `book` is played without searching, as long as it
+/// is legal in `state`.
+pub fn best_move(
+    state: &State,
+    depth: usize,
+    eval: &impl Evaluator,
+    book: Option<&dyn OpeningBook>,
+) -> Option<Move> {
+    best_move_with_extension(state, depth, 0, eval, book)
+}
+
+/// Like [`best_move`], but lines that reach the search horizon with either queen at most one
+/// liberty from being surrounded are searched up to `extension` plies deeper.
+pub fn best_move_with_extension(
+    state: &State,
+    depth: usize,
+    extension: usize,
+    eval: &impl Evaluator,
+    book: Option<&dyn OpeningBook>,
+) -> Option<Move> {
+    let successors = state.successors();
+    if let Some(mv) = book.and_then(|book| book.lookup(state))
+        && successors.iter().any(|(m, _)| *m == mv)
+    {
+        return Some(mv);
+    }
+
+    let mut alpha = FLOOR;
+    let mut best = None;
+    for (mv, child) in successors {
+        let score = -negamax(
+            &child,
+            depth.saturating_sub(1),
+            extension,
+            FLOOR,
+            -alpha,
+            eval,
+        );
+        if best.is_none() || score > alpha {
+            alpha = score;
+            best = Some(mv);
+        }
+    }
+    best
+}
+
+fn negamax(
+    state: &State,
+    depth: usize,
+    extension: usize,
+    mut alpha: i32,
+    beta: i32,
+    eval: &impl Evaluator,
+) -> i32 {
+    let sign = match state.active {
+        Player::P1 => 1,
+        Player::P2 => -1,
+    };
+    match state.result() {
+        GameResult::InProgress => {}
+        GameResult::Draw => return 0,
+        // prefer quicker wins and slower losses
+        GameResult::P1Wins => return sign * (WIN + depth as i32),
+        GameResult::P2Wins => return -sign * (WIN + depth as i32),
+    }
+    let noisy = state.queen_in_danger(Player::P1) || state.queen_in_danger(Player::P2);
+    let (depth, extension) = match depth {
+        0 if extension > 0 && noisy => (1, extension - 1),
+        depth => (depth, extension),
+    };
+    if depth == 0 {
+        return sign * eval.eval(state);
+    }
+
+    let mut best = FLOOR;
+    for (_, child) in state.successors() {
+        best = best.max(-negamax(&child, depth - 1, extension, -beta, -alpha, eval));
+        alpha = alpha.max(best);
+        if alpha >= beta {
+            break;
+        }
+    }
+    best
+}
+
+#[cfg(feature = "std")]
+struct Node {
+    mv: Option<Move>,
+    state: State,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    untried: Vec<(Move, State)>,
+    visits: u32,
+    /// Total score for the player who moved into this node, a win being 1 and a draw 0.5.
+    score: f64,
+}
+
+#[cfg(feature = "std")]
+impl Node {
+    fn new(mv: Option<Move>, state: State, parent: Option<usize>) -> Self {
+        let untried = match state.result() {
+            GameResult::InProgress => state.successors(),
+            _ => Vec::new(),
+        };
+        Self {
+            mv,
+            state,
+            parent,
+            children: Vec::new(),
+            untried,
+            visits: 0,
+            score: 0.0,
+        }
+    }
+
+    fn uct(&self, parent_visits: u32) -> f64 {
+        self.score / self.visits as f64
+            + EXPLORATION * ((parent_visits as f64).ln() / self.visits as f64).sqrt()
+    }
+}
+
+/// Picks a move for the side to move with Monte Carlo tree search, spending `iterations` random
+/// playouts. Returns the most visited move, or `None` if nothing was searched. Needs `std` for
+/// the floating point maths.
+#[cfg(feature = "std")]
+pub fn mcts_best_move<R: Rng + ?Sized>(
+    state: &State,
+    iterations: usize,
+    rng: &mut R,
+) -> Option<Move> {
+    let mut tree = vec![Node::new(None, state.clone(), None)];
+    for _ in 0..iterations {
+        let mut node = 0;
+        while tree[node].untried.is_empty() && !tree[node].children.is_empty() {
+            let visits = tree[node].visits;
+            node = *tree[node]
+                .children
+                .iter()
+                .max_by(|&&a, &&b| tree[a].uct(visits).total_cmp(&tree[b].uct(visits)))
+                .unwrap();
+        }
+
+        if !tree[node].untried.is_empty() {
+            let i = rng.random_range(..tree[node].untried.len());
+            let (mv, child) = tree[node].untried.swap_remove(i);
+            tree.push(Node::new(Some(mv), child, Some(node)));
+            let child = tree.len() - 1;
+            tree[node].children.push(child);
+            node = child;
+        }
+
+        let winner = tree[node].state.random_playout(rng, PLAYOUT_PLIES).winner();
+        let mut current = Some(node);
+        while let Some(n) = current {
+            let mover = !tree[n].state.active;
+            tree[n].visits += 1;
+            tree[n].score += match winner {
+                Some(winner) if winner == mover => 1.0,
+                Some(_) => 0.0,
+                None => 0.5,
+            };
+            current = tree[n].parent;
+        }
+    }
+
+    tree[0]
+        .children
+        .iter()
+        .max_by_key(|&&child| tree[child].visits)
+        .and_then(|&child| tree[child].mv)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use rand::{SeedableRng, rngs::SmallRng};
+
+    use super::*;
+    use crate::{Board, Piece, PieceKind, Pieces, Point, eval::MaterialEvaluator};
+
+    fn almost_surrounded() -> State {
+        let mut board = Board::default();
+        board.place(Point::new(0, 0, 0), Piece::Queen(Player::P2));
+        board.place(Point::new(-1, 0, 0), Piece::Queen(Player::P1));
+        board.place(Point::new(0, -1, 0), Piece::Grasshopper(Player::P1));
+        board.place(Point::new(0, 0, -1), Piece::Grasshopper(Player::P1));
+        board.place(Point::new(1, 0, 0), Piece::Spider(Player::P1));
+        board.place(Point::new(0, 1, 0), Piece::Spider(Player::P1));
+        board.place(Point::new(-1, 0, 1), Piece::Ant(Player::P1));
+        State::default()
+            .with_turn(3)
+            .with_queens(Some(Point::new(-1, 0, 0)), Some(Point::new(0, 0, 0)))
+            .with_board(board)
+    }
+
+    #[test]
+    fn test_best_move_finds_win() {
+        let state = almost_surrounded();
+
+        let mv = best_move(&state, 1, &|_: &State| 0, None).unwrap();
+
+        assert_eq!(
+            mv,
+            Move::Relocate {
+                kind: PieceKind::Ant,
+                from: Point::new(-1, 0, 1),
+                to: Point::new(0, 0, 1),
+            }
+        );
+    }
+
+    #[test]
+    fn test_best_move_delays_loss() {
+        // every white move loses, but the two beetle moves next to the black queen hold out two
+        // plies longer than the others
+        let state = State::from_position_string(
+            "w 9 - - -1,0,0:wB;0,-1,-1:bG;0,0,-1:bQ;0,0,1:wG;0,1,0:wQ;0,1,1:wS;0,1,2:bG;0,2,0:bS;\
+             0,3,0:wG;1,0,0:bB",
+        )
+        .unwrap();
+        let slower = [Point::new(0, 0, 1), Point::new(-1, 0, 1)].map(|to| Move::Relocate {
+            kind: PieceKind::Beetle,
+            from: Point::new(-1, 0, 0),
+            to,
+        });
+        let mv = best_move(&state, 4, &|_: &State| 0, None).unwrap();
+        assert!(slower.contains(&mv), "{mv:?}");
+
+        // after black's best reply every move loses at once, and a lost node deeper in the tree
+        // still reports how far off the loss is
+        let state = state
+            .apply(slower[1])
+            .unwrap()
+            .apply(Move::Relocate {
+                kind: PieceKind::Grasshopper,
+                from: Point::new(0, -1, -1),
+                to: Point::new(1, 1, 0),
+            })
+            .unwrap();
+        assert_eq!(
+            negamax(&state, 4, 0, FLOOR, -FLOOR, &|_: &State| 0),
+            -(WIN + 2)
+        );
+    }
+
+    #[test]
+    fn test_best_move_uses_book() {
+        let state = State::default();
+        let booked = Move::Place {
+            kind: PieceKind::Spider,
+            to: Point::new(0, 0, 0),
+        };
+        let mut book = HashMapBook::new();
+        book.insert(state.clone(), booked);
+        let evaluations = Cell::new(0);
+
+        let mv = best_move(
+            &state,
+            2,
+            &|_: &State| {
+                evaluations.set(evaluations.get() + 1);
+                0
+            },
+            Some(&book),
+        );
+
+        assert_eq!(mv, Some(booked));
+        assert_eq!(evaluations.get(), 0);
+    }
+
+    #[test]
+    fn test_book_symmetric_positions() {
+        let state = State::default()
+            .place(PieceKind::Queen, Point::new(0, 0, 0))
+            .unwrap()
+            .place(PieceKind::Queen, Point::new(1, 0, 0))
+            .unwrap();
+        let booked = Move::Place {
+            kind: PieceKind::Ant,
+            to: Point::new(-1, 0, 0),
+        };
+        let mut book = HashMapBook::new();
+        assert_eq!(book.insert(state.clone(), booked), None);
+
+        let moved = |p1: Point, p2: Point| {
+            let mut board = Board::default();
+            board.place(p1, Piece::Queen(Player::P1));
+            board.place(p2, Piece::Queen(Player::P2));
+            state
+                .clone()
+                .with_board(board)
+                .with_queens(Some(p1), Some(p2))
+        };
+        // the booked hex moves along with the hive
+        let translated = moved(Point::new(2, 0, 1), Point::new(3, 0, 1));
+        let expected = Move::Place {
+            kind: PieceKind::Ant,
+            to: Point::new(1, 0, 1),
+        };
+        assert_eq!(book.lookup(&translated), Some(expected));
+        let turned = moved(Point::new(0, 0, 0), Point::new(-1, 0, 0));
+        let expected = Move::Place {
+            kind: PieceKind::Ant,
+            to: Point::new(1, 0, 0),
+        };
+        assert_eq!(book.lookup(&turned), Some(expected));
+        assert_eq!(book.lookup(&State::default()), None);
+
+        assert_eq!(book.insert(turned, Move::Pass), Some(expected));
+        assert_eq!(book.lookup(&state), Some(Move::Pass));
+    }
+
+    #[test]
+    fn test_best_move_custom_evaluator() {
+        struct QueenFirst;
+
+        impl Evaluator for QueenFirst {
+            fn eval(&self, state: &State) -> i32 {
+                state.is_queen_placed(Player::P1) as i32
+            }
+        }
+
+        let mv = best_move(&State::default(), 1, &QueenFirst, None).unwrap();
+
+        assert_eq!(mv.piece_kind(), Some(PieceKind::Queen));
+        assert_ne!(
+            best_move(&State::default(), 1, &MaterialEvaluator::default(), None),
+            None
+        );
+    }
+
+    #[test]
+    fn test_move_cache() {
+        let mut cache = MoveCache::new();
+        let state = almost_surrounded();
+
+        let first = cache.legal_moves(&state).to_vec();
+        let second = cache.legal_moves(&state.clone()).to_vec();
+
+        assert_eq!(first, state.legal_moves());
+        assert_eq!(first, second);
+        assert_eq!(cache.generated(), 1);
+
+        cache.legal_moves(&State::default());
+        assert_eq!(cache.generated(), 2);
+        assert_eq!(cache.len(), 2);
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_extension_finds_win() {
+        let ring = Point::new(0, 0, 0).neighbors();
+        let mut board = Board::default();
+        board.place(Point::new(0, 0, 0), Piece::Queen(Player::P2));
+        board.place(ring[1], Piece::Queen(Player::P1));
+        board.place(ring[2], Piece::Spider(Player::P1));
+        board.place(ring[4], Piece::Spider(Player::P1));
+        board.place(ring[5], Piece::Grasshopper(Player::P1));
+        board.place(ring[2].neighbors()[2], Piece::Ant(Player::P1));
+        board.place(ring[4].neighbors()[4], Piece::Ant(Player::P1));
+        let state = State::default()
+            .with_turn(6)
+            .with_queens(Some(ring[1]), Some(Point::new(0, 0, 0)))
+            .with_unplaced(Pieces::from_counts(&HashMap::new(), Player::P1))
+            .with_board(board);
+        // a deliberately short-sighted eval that wants to keep the enemy queen free
+        let eval = |state: &State| 10 * state.liberties(Player::P2).unwrap_or(0) as i32;
+        let fills_ring =
+            |mv: Move| mv.destination() == Some(ring[0]) || mv.destination() == Some(ring[3]);
+
+        let shallow = best_move(&state, 1, &eval, None).unwrap();
+        let extended = best_move_with_extension(&state, 1, 2, &eval, None).unwrap();
+
+        assert!(!fills_ring(shallow));
+        assert!(fills_ring(extended));
+    }
+
+    #[test]
+    fn test_mcts_finds_win() {
+        // P1's grasshopper can jump over the queen into the last gap around her
+        let ring = Point::new(0, 0, 0).neighbors();
+        let mut board = Board::default();
+        board.place(Point::new(0, 0, 0), Piece::Queen(Player::P2));
+        for &p in &ring[1..] {
+            board.place(p, Piece::Spider(Player::P2));
+        }
+        board.place(Point::new(2, 0, 0), Piece::Grasshopper(Player::P1));
+        board.place(ring[1].neighbors()[1], Piece::Queen(Player::P1));
+        let state = State::default()
+            .with_turn(6)
+            .with_queens(Some(ring[1].neighbors()[1]), Some(Point::new(0, 0, 0)))
+            .with_unplaced(Pieces::from_counts(&HashMap::new(), Player::P1))
+            .with_board(board);
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        let mv = mcts_best_move(&state, 50, &mut rng).unwrap();
+
+        assert!(state.successors().len() > 1);
+        assert!(state.successors().iter().any(|(m, _)| *m == mv));
+        assert_eq!(state.apply(mv).unwrap().result(), GameResult::P1Wins);
+        assert_eq!(mcts_best_move(&state, 0, &mut rng), None);
+    }
+}
@@ -0,0 +1,818 @@
+//! Support for the [Universal Hive Protocol](https://github.com/jonthysell/Mzinga/wiki/UniversalHiveProtocol).
+//!
+//! Pieces of the same colour and kind are numbered from 1 in the order they were placed, so an
+//! identifier like `wA2` keeps meaning the same piece as it moves around. Pieces whose placement
+//! the state didn't see, like those on a board set up directly, come after the rest in board
+//! order.
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+};
+
+use itertools::Itertools;
+
+use crate::{
+    Game, GameResult, HiveError, Move, Piece, PieceKind, Player, Point, State, eval, search,
+};
+
+/// The notation for a position next to a reference piece, indexed by
+/// [`Direction`](crate::Direction). The bool is whether the symbol goes after the reference piece
+/// rather than before it.
+const DIRECTIONS: [(char, bool); 6] = [
+    ('-', false),
+    ('/', false),
+    ('\\', true),
+    ('-', true),
+    ('/', true),
+    ('\\', false),
+];
+
+const DEFAULT_DEPTH: usize = 2;
+
+impl State {
+    /// The UHP identifier of the top piece at `point`, e.g. `wA2`. Pieces of the same colour and
+    /// kind are numbered in the order they were placed.
+    pub fn piece_id(&self, point: Point) -> Option<String> {
+        let height = self.board.get(&point)?.height() - 1;
+        piece_ids(self)
+            .into_iter()
+            .find(|(_, p, h)| *p == point && *h == height)
+            .map(|(id, _, _)| id)
+    }
+}
+
+impl Move {
+    /// Parses a UHP move string such as `wS1`, `bA1 -wS1`, `wB1 bQ`, or `pass` in the context of
+    /// `state`.
+    pub fn from_uhp(s: &str, state: &State) -> Result<Move, HiveError> {
+        let invalid = || HiveError::InvalidNotation(s.to_string());
+        let s = s.trim();
+        if s == "pass" {
+            return Ok(Move::Pass);
+        }
+
+        let ids: HashMap<_, _> = piece_ids(state)
+            .into_iter()
+            .map(|(id, point, _)| (id, point))
+            .collect();
+        let (piece, reference) = match s.split_whitespace().collect_vec()[..] {
+            [piece] => (piece, None),
+            [piece, reference] => (piece, Some(reference)),
+            _ => return Err(invalid()),
+        };
+        let (player, kind, number) = parse_id(piece).ok_or_else(invalid)?;
+        if player != state.active {
+            return Err(HiveError::WrongPlayer(player));
+        }
+
+        let to = match reference {
+            None if state.board.is_empty() => Point::new(0, 0, 0),
+            None => return Err(invalid()),
+            Some(reference) => {
+                let direction = DIRECTIONS.iter().position(|&(symbol, after)| {
+                    if after {
+                        reference.ends_with(symbol)
+                    } else {
+                        reference.starts_with(symbol)
+                    }
+                });
+                let id = match direction {
+                    Some(d) if DIRECTIONS[d].1 => &reference[..reference.len() - 1],
+                    Some(_) => &reference[1..],
+                    None => reference,
+                };
+                parse_id(id).ok_or_else(invalid)?;
+                let &point = ids.get(id).ok_or_else(invalid)?;
+                match direction {
+                    Some(direction) => point.neighbors()[direction],
+                    None => point,
+                }
+            }
+        };
+
+        if let Some(&from) = ids.get(piece) {
+            return Ok(Move::Relocate { kind, from, to });
+        }
+        // a new piece has to be the next of its kind, and there has to be one left in hand
+        let placed = ids
+            .keys()
+            .filter(|id| parse_id(id).is_some_and(|(p, k, _)| (p, k) == (player, kind)))
+            .count();
+        if number.is_some_and(|n| n != placed + 1) || !state.unplaced.kinds(player).contains(&kind)
+        {
+            return Err(invalid());
+        }
+        Ok(Move::Place { kind, to })
+    }
+
+    /// Formats this move in UHP notation. `state` is the position the move is played from.
+    pub fn to_uhp(&self, state: &State) -> String {
+        let ids = piece_ids(state);
+        let id_at = |point: Point, height: usize| {
+            ids.iter()
+                .find(|(_, p, h)| *p == point && *h == height)
+                .map(|(id, _, _)| id.clone())
+        };
+
+        let (piece, from, to) = match *self {
+            Move::Pass => return "pass".to_string(),
+            Move::Place { kind, to } => {
+                let count = ids
+                    .iter()
+                    .filter(|(id, _, _)| {
+                        parse_id(id).is_some_and(|(p, k, _)| (p, k) == (state.active, kind))
+                    })
+                    .count();
+                let piece = format_id(state.active, kind, count + 1);
+                (piece, None, to)
+            }
+            Move::Relocate { from, to, .. } => {
+                (state.piece_id(from).unwrap_or_default(), Some(from), to)
+            }
+        };
+
+        if let Some(reference) = state.piece_id(to) {
+            return format!("{piece} {reference}");
+        }
+        // prefer a neighbour other than the piece that's moving, but fall back to whatever it
+        // leaves behind
+        let neighbors = to.neighbors_with_direction();
+        let reference = neighbors
+            .iter()
+            .filter(|&&(p, _)| Some(p) != from)
+            .chain(&neighbors)
+            .find_map(|&(p, direction)| {
+                let height = (state.board.get(&p)?.height() - 1)
+                    .checked_sub(usize::from(Some(p) == from))?;
+                Some((id_at(p, height)?, direction.opposite()))
+            });
+        match reference {
+            Some((reference, direction)) => match DIRECTIONS[direction as usize] {
+                (symbol, true) => format!("{piece} {reference}{symbol}"),
+                (symbol, false) => format!("{piece} {symbol}{reference}"),
+            },
+            None => piece,
+        }
+    }
+}
+
+/// Runs a UHP engine, reading commands from `input` until it's exhausted and writing responses
+/// to `output`.
+pub fn run(input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    let mut engine = Engine::default();
+    writeln!(output, "{}\nok", engine.info())?;
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        let response = match command {
+            "info" => Ok(engine.info()),
+            "newgame" => engine.new_game(args),
+            "play" => engine.play(args),
+            "pass" => engine.play("pass"),
+            "validmoves" => Ok(engine.valid_moves()),
+            "bestmove" => engine.best_move(args),
+            "undo" => engine.undo(args),
+            "options" => engine.options(args),
+            _ => Err(format!("err unknown command {command:?}")),
+        };
+        match response {
+            Ok(response) if response.is_empty() => writeln!(output, "ok")?,
+            Ok(response) => writeln!(output, "{response}\nok")?,
+            Err(error) => writeln!(output, "{error}\nok")?,
+        }
+    }
+    Ok(())
+}
+
+/// A game driven by UHP move strings, the stateful core of an interactive front end.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Session {
+    game: Game,
+}
+
+impl Session {
+    pub fn new(state: State) -> Self {
+        Self {
+            game: Game::new(state),
+        }
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn state(&self) -> &State {
+        self.game.state()
+    }
+
+    /// Plays a move given in UHP notation, like `wA1 -bQ`.
+    pub fn play_str(&mut self, mv: &str) -> Result<(), HiveError> {
+        let mv = Move::from_uhp(mv, self.game.state())?;
+        self.game.play(mv)
+    }
+
+    /// Takes back the last move, returning it, or `None` if no moves have been played.
+    pub fn undo(&mut self) -> Option<Move> {
+        self.game.undo()
+    }
+
+    /// The UHP GameString, e.g. `Base;InProgress;White[2];wS1;bS1 \wS1`.
+    pub fn status(&self) -> String {
+        let state = self.game.state();
+        let color = match state.active {
+            Player::P1 => "White",
+            Player::P2 => "Black",
+        };
+        [
+            "Base".to_string(),
+            game_state(&self.game).to_string(),
+            format!("{color}[{}]", state.turn + 1),
+        ]
+        .into_iter()
+        .chain(move_strings(&self.game))
+        .join(";")
+    }
+}
+
+impl Game {
+    /// Reads a game from a PGN-style transcript of the kind BoardSpace and other servers share:
+    /// `[Tag "value"]` header lines, then one numbered UHP move per line, e.g. `3. wQ /wS1`.
+    /// Only the Base game type is supported, and the other tags are ignored.
+    pub fn from_boardspace(s: &str) -> Result<Game, HiveError> {
+        let invalid = |line: &str| HiveError::InvalidNotation(line.to_string());
+        let mut game = Game::default();
+        for line in s.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            if let Some(tag) = line.strip_prefix('[') {
+                let (name, value) = tag
+                    .strip_suffix(']')
+                    .and_then(|tag| tag.split_once(' '))
+                    .ok_or_else(|| invalid(line))?;
+                if name == "GameType" && value.trim_matches('"') != "Base" {
+                    return Err(invalid(line));
+                }
+                continue;
+            }
+            let (number, mv) = line.split_once(". ").ok_or_else(|| invalid(line))?;
+            if number.parse() != Ok(game.history().len() + 1) {
+                return Err(invalid(line));
+            }
+            game.play(Move::from_uhp(mv, game.state())?)?;
+        }
+        Ok(game)
+    }
+
+    /// Writes the game in the format read by [`Game::from_boardspace`], with the game type and
+    /// result as tags.
+    pub fn to_boardspace(&self) -> String {
+        [
+            "[GameType \"Base\"]".to_string(),
+            format!("[Result \"{}\"]", game_state(self)),
+            String::new(),
+        ]
+        .into_iter()
+        .chain(
+            move_strings(self)
+                .enumerate()
+                .map(|(i, mv)| format!("{}. {mv}", i + 1)),
+        )
+        .join("\n")
+    }
+}
+
+/// The game state as UHP names it.
+fn game_state(game: &Game) -> &'static str {
+    match game.state().result() {
+        GameResult::InProgress if game.history().is_empty() => "NotStarted",
+        GameResult::InProgress => "InProgress",
+        GameResult::Draw => "Draw",
+        GameResult::P1Wins => "WhiteWins",
+        GameResult::P2Wins => "BlackWins",
+    }
+}
+
+fn move_strings(game: &Game) -> impl Iterator<Item = String> + '_ {
+    game.history().iter().map(|(state, mv)| mv.to_uhp(state))
+}
+
+#[derive(Debug, Default)]
+struct Engine {
+    session: Session,
+}
+
+impl Engine {
+    fn info(&self) -> String {
+        format!("id hive {}", env!("CARGO_PKG_VERSION"))
+    }
+
+    fn new_game(&mut self, args: &str) -> Result<String, String> {
+        let mut fields = args.split(';').map(str::trim);
+        if !matches!(fields.next(), None | Some("" | "Base")) {
+            return Err("err only the Base game type is supported".to_string());
+        }
+        // replay into a fresh engine so a bad move leaves the current game as it was
+        let mut engine = Self::default();
+        for mv in fields.skip(2) {
+            engine.play(mv)?;
+        }
+        *self = engine;
+        Ok(self.session.status())
+    }
+
+    fn play(&mut self, mv: &str) -> Result<String, String> {
+        if self.session.state().result().is_over() {
+            return Err("err the game is over".to_string());
+        }
+        self.session
+            .play_str(mv)
+            .map_err(|error| format!("invalidmove {error}"))?;
+        Ok(self.session.status())
+    }
+
+    fn valid_moves(&self) -> String {
+        let state = self.session.state();
+        state
+            .successors()
+            .into_iter()
+            .map(|(mv, _)| mv)
+            .sorted()
+            .map(|mv| mv.to_uhp(state))
+            .join(";")
+    }
+
+    fn best_move(&self, args: &str) -> Result<String, String> {
+        let depth = match args.split_whitespace().collect_vec()[..] {
+            ["depth", depth] => depth
+                .parse()
+                .map_err(|_| format!("err invalid depth {depth:?}"))?,
+            ["time", _] | [] => DEFAULT_DEPTH,
+            _ => return Err(format!("err invalid arguments {args:?}")),
+        };
+        let state = self.session.state();
+        search::best_move(state, depth, &eval::DefaultEvaluator, None)
+            .map(|mv| mv.to_uhp(state))
+            .ok_or_else(|| "err no moves available".to_string())
+    }
+
+    fn undo(&mut self, args: &str) -> Result<String, String> {
+        let count = match args.trim() {
+            "" => 1,
+            n => n.parse().map_err(|_| format!("err invalid count {n:?}"))?,
+        };
+        if count > self.session.game().history().len() {
+            return Err(format!("err cannot undo {count} moves"));
+        }
+        for _ in 0..count {
+            self.session.undo();
+        }
+        Ok(self.session.status())
+    }
+
+    fn options(&self, args: &str) -> Result<String, String> {
+        match args.trim() {
+            "" => Ok(String::new()),
+            _ => Err("err this engine has no options".to_string()),
+        }
+    }
+}
+
+fn parse_id(id: &str) -> Option<(Player, PieceKind, Option<usize>)> {
+    let mut chars = id.chars();
+    let player = Player::from_letter(chars.next()?)?;
+    let kind = PieceKind::from_letter(chars.next()?)?;
+    let number = match chars.as_str() {
+        "" if is_unique(kind) => None,
+        n if !is_unique(kind) => Some(n.parse().ok().filter(|&n| n > 0)?),
+        _ => return None,
+    };
+    Some((player, kind, number))
+}
+
+fn format_id(player: Player, kind: PieceKind, number: usize) -> String {
+    if is_unique(kind) {
+        Piece::new(kind, player).ascii()
+    } else {
+        format!("{}{number}", Piece::new(kind, player).ascii())
+    }
+}
+
+/// Kinds with one piece per player, whose identifiers don't get a number.
+fn is_unique(kind: PieceKind) -> bool {
+    matches!(kind, PieceKind::Queen | PieceKind::Mosquito)
+}
+
+/// Every piece on the board with its identifier, position, and height in its stack.
+fn piece_ids(state: &State) -> Vec<(String, Point, usize)> {
+    state
+        .board
+        .iter()
+        .flat_map(|(&point, stack)| {
+            stack
+                .iter()
+                .enumerate()
+                .map(move |(height, piece)| (piece.player(), piece.kind(), point, height))
+        })
+        .sorted_by_key(|&(player, kind, point, height)| {
+            let rank = state.placed.rank(&state.board, point, height);
+            (player, kind, rank.unwrap_or(usize::MAX), point, height)
+        })
+        .chunk_by(|&(player, kind, _, _)| (player, kind))
+        .into_iter()
+        .flat_map(|(_, pieces)| {
+            pieces
+                .enumerate()
+                .map(|(i, (player, kind, point, height))| {
+                    (format_id(player, kind, i + 1), point, height)
+                })
+                .collect_vec()
+        })
+        .collect_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piece;
+
+    #[test]
+    fn test_apply_uhp() {
+        let state = ["wS1", "bS1 \\wS1", "wQ /wS1", "bQ bS1/"]
+            .into_iter()
+            .try_fold(State::default(), |state, mv| state.apply_uhp(mv))
+            .unwrap();
+
+        assert_eq!(state.board.len(), 4);
+        assert_eq!(
+            state.board.top(&Point::new(0, 0, 0)),
+            Some(&Piece::Spider(Player::P1))
+        );
+        assert_eq!(
+            state.board.top(&Point::new(0, 0, 1)),
+            Some(&Piece::Spider(Player::P2))
+        );
+        assert_eq!(
+            state.board.top(&Point::new(0, -1, 0)),
+            Some(&Piece::Queen(Player::P1))
+        );
+        assert_eq!(
+            state.board.top(&Point::new(0, 1, 1)),
+            Some(&Piece::Queen(Player::P2))
+        );
+
+        let state = state.apply_uhp("wQ wS1\\").unwrap();
+        assert_eq!(
+            state.board.top(&Point::new(0, 0, -1)),
+            Some(&Piece::Queen(Player::P1))
+        );
+        assert!(!state.board.contains_key(&Point::new(0, -1, 0)));
+    }
+
+    #[test]
+    fn test_apply_uhp_errors() {
+        let state = State::default().apply_uhp("wS1").unwrap();
+
+        assert!(matches!(
+            state.apply_uhp("bX1 wS1-"),
+            Err(HiveError::InvalidNotation(_))
+        ));
+        assert!(matches!(
+            state.apply_uhp("bS1 wA1-"),
+            Err(HiveError::InvalidNotation(_))
+        ));
+        assert!(matches!(
+            state.apply_uhp("bS1 \\wS1").unwrap().apply_uhp("wA1 -bS1"),
+            Err(HiveError::IllegalMove(_))
+        ));
+        assert_eq!(
+            state.apply_uhp("wA1 wS1-"),
+            Err(HiveError::WrongPlayer(Player::P1))
+        );
+
+        // new pieces come in numbered order, and only from the hand
+        for mv in ["bS2 wS1-", "bA3 wS1-", "bQ2 wS1-"] {
+            assert_eq!(
+                state.apply_uhp(mv),
+                Err(HiveError::InvalidNotation(mv.to_string()))
+            );
+        }
+        let state = state.apply_uhp("bS1 wS1-").unwrap();
+        assert!(matches!(
+            state.apply_uhp("wS3 -wS1"),
+            Err(HiveError::InvalidNotation(_))
+        ));
+        let state = state
+            .apply_uhp("wS2 -wS1")
+            .unwrap()
+            .apply_uhp("bS2 bS1-")
+            .unwrap();
+        assert!(matches!(
+            state.apply_uhp("wS3 -wS2"),
+            Err(HiveError::InvalidNotation(_))
+        ));
+    }
+
+    #[test]
+    fn test_to_uhp_round_trip() {
+        let state = ["wS1", "bS1 \\wS1", "wQ /wS1", "bQ bS1/", "wB1 wQ\\"]
+            .into_iter()
+            .try_fold(State::default(), |state, mv| state.apply_uhp(mv))
+            .unwrap();
+
+        for (mv, _) in state.successors() {
+            assert_eq!(Move::from_uhp(&mv.to_uhp(&state), &state), Ok(mv));
+        }
+    }
+
+    #[test]
+    fn test_to_uhp_round_trip_after_moves() {
+        let mut session = Session::default();
+        for mv in [
+            "wQ", "bQ wQ-", "wA1 -wQ", "bA1 bQ-", "wA2 -wA1", "bA2 bA1-", "wA2 \\wQ",
+        ] {
+            session.play_str(mv).unwrap();
+        }
+        // the second ant has walked round to a point that sorts after the first one
+        let state = session.state().clone();
+        let second = state
+            .board
+            .iter()
+            .find(|(point, _)| state.piece_id(**point).as_deref() == Some("wA2"))
+            .map(|(&point, _)| point)
+            .unwrap();
+        let first = Point::new(-1, 0, 0);
+        assert!(second > first);
+        assert_eq!(state.piece_id(first).as_deref(), Some("wA1"));
+
+        for (mv, _) in state.successors() {
+            assert_eq!(Move::from_uhp(&mv.to_uhp(&state), &state), Ok(mv));
+        }
+        let black = state.apply_uhp("bA2 -wA2").unwrap();
+        for (mv, _) in black.successors() {
+            assert_eq!(Move::from_uhp(&mv.to_uhp(&black), &black), Ok(mv));
+        }
+        let Ok(Move::Relocate { from, .. }) = Move::from_uhp("wA2 /wQ", &black) else {
+            panic!();
+        };
+        assert_eq!(from, second);
+    }
+
+    #[test]
+    fn test_piece_id() {
+        let state = [
+            "wS1",
+            "bS1 \\wS1",
+            "wQ /wS1",
+            "bQ bS1/",
+            "wB1 wQ\\",
+            "bA1 bQ-",
+        ]
+        .into_iter()
+        .try_fold(State::default(), |state, mv| state.apply_uhp(mv))
+        .unwrap();
+        let ids = state
+            .board
+            .keys()
+            .map(|&point| state.piece_id(point).unwrap())
+            .collect_vec();
+
+        assert_eq!(ids.iter().unique().count(), ids.len());
+        assert!(
+            state
+                .board
+                .keys()
+                .zip(&ids)
+                .all(|(&point, id)| state.piece_id(point).as_ref() == Some(id))
+        );
+        assert_eq!(state.piece_id(Point::new(0, -1, 0)), Some("wQ".to_string()));
+        assert_eq!(state.piece_id(Point::new(5, 0, 0)), None);
+    }
+
+    #[test]
+    fn test_piece_id_placement_order() {
+        let (first, second) = (Point::new(-1, 0, 0), Point::new(-2, 0, 0));
+        let state = State::default()
+            .place(PieceKind::Queen, Point::new(0, 0, 0))
+            .unwrap()
+            .place(PieceKind::Queen, Point::new(1, 0, 0))
+            .unwrap()
+            .place(PieceKind::Ant, first)
+            .unwrap()
+            .place(PieceKind::Ant, Point::new(2, 0, 0))
+            .unwrap();
+        // the second ant lands at a point that sorts before the first one
+        let state = state.place(PieceKind::Ant, second).unwrap();
+        assert!(second < first);
+        assert_eq!(state.piece_id(first).as_deref(), Some("wA1"));
+        assert_eq!(state.piece_id(second).as_deref(), Some("wA2"));
+
+        let state = state.place(PieceKind::Ant, Point::new(3, 0, 0)).unwrap();
+        let destinations = state.legal_destinations(second);
+        assert!(destinations.iter().any(|&to| to < first));
+        assert!(destinations.iter().any(|&to| to > first));
+        for to in destinations {
+            let mv = Move::Relocate {
+                kind: PieceKind::Ant,
+                from: second,
+                to,
+            };
+            assert!(mv.to_uhp(&state).starts_with("wA2 "));
+            let moved = state.apply(mv).unwrap();
+            assert_eq!(moved.piece_id(first).as_deref(), Some("wA1"), "{to:?}");
+            assert_eq!(moved.piece_id(to).as_deref(), Some("wA2"), "{to:?}");
+            assert_eq!(moved.piece_id(Point::new(2, 0, 0)).as_deref(), Some("bA1"));
+            assert_eq!(moved.piece_id(Point::new(3, 0, 0)).as_deref(), Some("bA2"));
+        }
+
+        // a board set up by hand falls back to board order
+        let unseen = State::default().with_board(state.board.clone());
+        assert_eq!(unseen.piece_id(second).as_deref(), Some("wA1"));
+    }
+
+    #[test]
+    fn test_session() {
+        let mut session = Session::default();
+        assert_eq!(session.status(), "Base;NotStarted;White[1]");
+
+        session.play_str("wS1").unwrap();
+        session.play_str("bS1 \\wS1").unwrap();
+        session.play_str("wQ /wS1").unwrap();
+        assert_eq!(
+            session.status(),
+            "Base;InProgress;Black[2];wS1;bS1 \\wS1;wQ /wS1"
+        );
+        assert!(matches!(
+            session.play_str("wA1 wQ-"),
+            Err(HiveError::WrongPlayer(Player::P1))
+        ));
+        assert_eq!(session.game().history().len(), 3);
+
+        assert_eq!(
+            session.undo(),
+            Some(Move::Place {
+                kind: PieceKind::Queen,
+                to: Point::new(0, -1, 0),
+            })
+        );
+        assert_eq!(session.status(), "Base;InProgress;White[2];wS1;bS1 \\wS1");
+        session.undo();
+        session.undo();
+        assert_eq!(session.undo(), None);
+        assert_eq!(session.state(), &State::default());
+    }
+
+    #[test]
+    fn test_boardspace_moves() {
+        // both white ants move, and the second ends up past the first in board order
+        let moves = "\
+1. wQ
+2. bQ wQ-
+3. wA1 -wQ
+4. bA1 bQ-
+5. wA2 -wA1
+6. bA2 bA1-
+7. wA2 wA1/
+8. bA2 \\wA1
+9. wA1 bA1/
+10. bA2 \\wA2";
+        let transcript = format!("[GameType \"Base\"]\n[Event \"Casual\"]\n\n{moves}");
+
+        let game = Game::from_boardspace(&transcript).unwrap();
+        assert_eq!(game.history().len(), 10);
+        let Move::Relocate { to, .. } = game.history()[6].1 else {
+            panic!();
+        };
+        assert!(to > Point::new(-1, 0, 0));
+        let written = game.to_boardspace();
+        assert!(written.ends_with(moves), "{written}");
+        assert_eq!(Game::from_boardspace(&written), Ok(game));
+    }
+
+    #[test]
+    fn test_boardspace() {
+        let transcript = "\
+            [GameType \"Base\"]
+            [Event \"Casual\"]
+            [White \"alice\"]
+            [Black \"bob\"]
+            [Result \"InProgress\"]
+
+            1. wS1
+            2. bS1 \\wS1
+            3. wQ /wS1
+            4. bQ bS1/
+            5. wB1 wQ\\
+        ";
+
+        let game = Game::from_boardspace(transcript).unwrap();
+
+        assert_eq!(game.history().len(), 5);
+        assert_eq!(
+            game.history()[2].1,
+            Move::Place {
+                kind: PieceKind::Queen,
+                to: Point::new(0, -1, 0),
+            }
+        );
+        let written = game.to_boardspace();
+        assert!(written.starts_with("[GameType \"Base\"]\n[Result \"InProgress\"]\n\n1. wS1\n"));
+        assert!(written.ends_with("\n5. wB1 wQ\\"));
+        assert_eq!(Game::from_boardspace(&written), Ok(game));
+        assert_eq!(Game::from_boardspace(""), Ok(Game::default()));
+
+        for bad in [
+            "[GameType \"Base+M\"]",
+            "[GameType]",
+            "2. wS1",
+            "wS1",
+            "1. wS1\n2. wS2 -wS1",
+        ] {
+            assert!(Game::from_boardspace(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        let input = "info\nnewgame\nplay wS1\nvalidmoves\nplay wQ wS1-\nplay bQ \\wS1\nundo\noptions\nfoo\n";
+        let mut output = Vec::new();
+
+        run(input.as_bytes(), &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let id = format!("id hive {}", env!("CARGO_PKG_VERSION"));
+        assert_eq!(
+            output.lines().collect_vec(),
+            vec![
+                id.as_str(),
+                "ok",
+                id.as_str(),
+                "ok",
+                "Base;NotStarted;White[1]",
+                "ok",
+                "Base;InProgress;Black[1];wS1",
+                "ok",
+                "bQ -wS1;bQ /wS1;bQ wS1\\;bQ \\wS1;bQ wS1/;bQ wS1-;\
+                 bB1 -wS1;bB1 /wS1;bB1 wS1\\;bB1 \\wS1;bB1 wS1/;bB1 wS1-;\
+                 bA1 -wS1;bA1 /wS1;bA1 wS1\\;bA1 \\wS1;bA1 wS1/;bA1 wS1-;\
+                 bG1 -wS1;bG1 /wS1;bG1 wS1\\;bG1 \\wS1;bG1 wS1/;bG1 wS1-;\
+                 bS1 -wS1;bS1 /wS1;bS1 wS1\\;bS1 \\wS1;bS1 wS1/;bS1 wS1-",
+                "ok",
+                "invalidmove it is not P1's turn",
+                "ok",
+                "Base;InProgress;White[2];wS1;bQ \\wS1",
+                "ok",
+                "Base;InProgress;Black[1];wS1",
+                "ok",
+                "ok",
+                "err unknown command \"foo\"",
+                "ok",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_rejected_newgame() {
+        let input =
+            "play wS1\nplay bS1 wS1-\nnewgame Base;InProgress;White[3];wS1;bad\nvalidmoves\n";
+        let mut output = Vec::new();
+
+        run(input.as_bytes(), &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines = output.lines().collect_vec();
+        let state = ["wS1", "bS1 wS1-"]
+            .into_iter()
+            .try_fold(State::default(), |state, mv| state.apply_uhp(mv))
+            .unwrap();
+        let moves = state
+            .successors()
+            .into_iter()
+            .map(|(mv, _)| mv)
+            .sorted()
+            .map(|mv| mv.to_uhp(&state))
+            .join(";");
+        assert_eq!(lines[4..6], ["Base;InProgress;White[2];wS1;bS1 wS1-", "ok"]);
+        assert!(lines[6].starts_with("invalidmove "), "{}", lines[6]);
+        assert_eq!(lines[7..], ["ok", moves.as_str(), "ok"]);
+    }
+
+    #[test]
+    fn test_run_bestmove() {
+        let input = "newgame Base;InProgress;White[2];wS1;bS1 \\wS1\nbestmove depth 1\n";
+        let mut output = Vec::new();
+
+        run(input.as_bytes(), &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines = output.lines().collect_vec();
+        let state = ["wS1", "bS1 \\wS1"]
+            .into_iter()
+            .try_fold(State::default(), |state, mv| state.apply_uhp(mv))
+            .unwrap();
+        assert_eq!(lines[2], "Base;InProgress;White[2];wS1;bS1 \\wS1");
+        assert!(state.apply_uhp(lines[4]).is_ok());
+    }
+}